@@ -0,0 +1,192 @@
+use std::str::FromStr;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use crate::error::{Result, TaskustError};
+use crate::models::Task;
+
+/// Time a task is due when it has a due date but no time: the end of the day.
+pub const DEFAULT_DUE_TIME: NaiveTime = match NaiveTime::from_hms_opt(23, 59, 0) {
+    Some(t) => t,
+    None => unreachable!(),
+};
+
+/// The timezone dates and due times are in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    /// The machine's local timezone.
+    Local,
+    /// An IANA timezone such as `Europe/Berlin`.
+    Named(Tz),
+}
+
+impl Zone {
+    /// Returns the current wall-clock time in this zone.
+    pub fn now(self) -> NaiveDateTime {
+        self.wall_clock(Utc::now())
+    }
+
+    /// Converts an instant to wall-clock time in this zone.
+    pub fn wall_clock(self, instant: DateTime<Utc>) -> NaiveDateTime {
+        self.offset_time(instant).naive_local()
+    }
+
+    /// Converts an instant to this zone, keeping the zone's offset at that instant.
+    pub fn offset_time(self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Local => instant.with_timezone(&Local).fixed_offset(),
+            Zone::Named(tz) => instant.with_timezone(&tz).fixed_offset(),
+        }
+    }
+
+    /// Converts a wall-clock time in this zone to an instant.
+    ///
+    /// Ambiguous times after a DST change use the earlier instant; times skipped by one are
+    /// read as UTC, which is off by at most the DST shift.
+    pub fn to_utc(self, time: NaiveDateTime) -> DateTime<Utc> {
+        let instant = match self {
+            Zone::Local => Local.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc)),
+            Zone::Named(tz) => tz.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc)),
+        };
+        instant.unwrap_or_else(|| Utc.from_utc_datetime(&time))
+    }
+}
+
+impl FromStr for Zone {
+    type Err = TaskustError;
+
+    fn from_str(s: &str) -> Result<Zone> {
+        match s.trim() {
+            "" | "local" => Ok(Zone::Local),
+            name => name.parse().map(Zone::Named).map_err(|_| {
+                TaskustError::InvalidArgument(format!(
+                    "Unknown timezone '{}'. Use an IANA name such as Europe/Berlin, or local.",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+/// The source of the current time for everything that depends on it: urgency, what is
+/// overdue or hidden, recurrence, archiving, the timer and the timestamps of tasks, the undo
+/// journal and the change history.
+///
+/// [`Clock::system`] reads the system time; [`Clock::fixed`] and [`Clock::as_of`] stop it at
+/// another time, to test date logic or preview the list as of another day. Each
+/// [`TaskStore`](crate::storage::TaskStore) carries one, see
+/// [`with_clock`](crate::storage::TaskStore::with_clock).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    zone: Zone,
+    due_time: NaiveTime,
+    /// Wall-clock time the clock is stopped at, if it is. It is read in `zone`, so changing
+    /// the zone keeps the time of day and moves the instant.
+    fixed: Option<NaiveDateTime>,
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock::system()
+    }
+}
+
+impl Clock {
+    /// Returns a clock that follows the system time in the machine's local timezone, with
+    /// tasks due at [`DEFAULT_DUE_TIME`].
+    pub fn system() -> Clock {
+        Clock { zone: Zone::Local, due_time: DEFAULT_DUE_TIME, fixed: None }
+    }
+
+    /// Returns a system clock in the timezone named by the `TASKS_TIMEZONE` environment
+    /// variable, with tasks due at the `TASKS_DUE_TIME` (`HH:MM`), see [`Clock::from_settings`].
+    pub fn from_env() -> Result<Clock> {
+        let zone = std::env::var("TASKS_TIMEZONE").ok();
+        let due_time = std::env::var("TASKS_DUE_TIME").ok();
+        Clock::from_settings(zone.as_deref(), due_time.as_deref())
+    }
+
+    /// Returns a system clock in timezone `zone` (an IANA name or `local`), with tasks due at
+    /// `due_time` (`HH:MM`). Unset settings keep the defaults of [`Clock::system`].
+    ///
+    /// Fails with [`TaskustError::InvalidArgument`] if either can't be parsed.
+    pub fn from_settings(zone: Option<&str>, due_time: Option<&str>) -> Result<Clock> {
+        let mut clock = Clock::system();
+        if let Some(zone) = zone {
+            clock.zone = zone.parse()?;
+        }
+        if let Some(time) = due_time {
+            clock.due_time = NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| {
+                TaskustError::InvalidArgument(format!("Invalid default due time '{}'. Use HH:MM, e.g. 17:00.", time))
+            })?;
+        }
+        Ok(clock)
+    }
+
+    /// Returns a clock stopped at `now`, a wall-clock time in the clock's zone.
+    ///
+    /// The zone is the machine's local timezone unless changed with [`Clock::with_zone`],
+    /// which keeps `now` as the wall-clock time: `Clock::fixed(t).with_zone(z)` is stopped
+    /// at `t` in `z`.
+    pub fn fixed(now: NaiveDateTime) -> Clock {
+        Clock { fixed: Some(now), ..Clock::system() }
+    }
+
+    /// Returns this clock moved to `date`, at the same time of day.
+    pub fn as_of(self, date: NaiveDate) -> Clock {
+        Clock { fixed: Some(date.and_time(self.now().time())), ..self }
+    }
+
+    /// Returns this clock in timezone `zone`.
+    ///
+    /// A stopped clock keeps the same wall-clock time, now read in `zone`, rather than the
+    /// same instant.
+    pub fn with_zone(self, zone: Zone) -> Clock {
+        Clock { zone, ..self }
+    }
+
+    /// Returns this clock with `time` as the time tasks without a due time are due.
+    pub fn with_due_time(self, time: NaiveTime) -> Clock {
+        Clock { due_time: time, ..self }
+    }
+
+    /// Returns the current wall-clock time in the clock's zone.
+    pub fn now(&self) -> NaiveDateTime {
+        self.fixed.unwrap_or_else(|| self.zone.now())
+    }
+
+    /// Returns the current instant.
+    pub fn now_utc(&self) -> DateTime<Utc> {
+        match self.fixed {
+            Some(now) => self.zone.to_utc(now),
+            None => Utc::now(),
+        }
+    }
+
+    /// Returns today's date in the clock's zone.
+    pub fn today(&self) -> NaiveDate {
+        self.now().date()
+    }
+
+    /// Returns the current time as an RFC 3339 timestamp with the offset of the clock's zone,
+    /// as stored in `created_at`, `completed_at`, the undo journal, the change history and
+    /// the timer.
+    pub fn timestamp(&self) -> String {
+        self.zone.offset_time(self.now_utc()).to_rfc3339()
+    }
+
+    /// Returns the date of `instant` in the clock's zone.
+    pub fn date_of(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.zone.wall_clock(instant).date()
+    }
+
+    /// Returns when `task` is due, as a wall-clock time in the clock's zone.
+    pub fn deadline(&self, task: &Task) -> NaiveDateTime {
+        task.due_date.and_time(task.due_time.unwrap_or(self.due_time))
+    }
+
+    /// Returns the hours left until `task` is due; negative once it is overdue.
+    pub fn hours_left(&self, task: &Task) -> f64 {
+        let left = self.zone.to_utc(self.deadline(task)) - self.now_utc();
+        left.num_seconds() as f64 / 3600.0
+    }
+}
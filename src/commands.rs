@@ -1,101 +1,349 @@
+use std::collections::BTreeMap;
 use std::io::{self, Write};
-use chrono::{Local, NaiveDate, Duration};
+use chrono::{NaiveDate, NaiveTime, Duration, Utc};
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{Attribute, Cell, Color, ContentArrangement, Table};
-use crate::models::{Task, Template};
-use crate::storage::{delete_database, load_task, load_tasks, load_template, load_templates, save_tasks, save_task, save_templates, append_to_archive};
-use crate::urgency::compute_urgency;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::error::{Result, TaskustError};
+use crate::history::{self, format_timestamp, format_value};
+use crate::journal::{diff_tasks, Change, Operation};
+use crate::models::{Annotation, ChecklistItem, Priority, Status, Task, Template, WorkEntry};
+use crate::storage::{Backend, TaskStore};
+use crate::timer::{format_elapsed, Timer};
+use crate::deps::{self, is_blocked};
+use crate::urgency::{self, available_hours, is_at_risk, sort_by_urgency, UrgencyConfig, UrgencyLevel, UrgencyStrategy};
+use uuid::Uuid;
+
+/// Fields for a new task, see [`cmd_add`].
+#[derive(Debug, Clone, Default)]
+pub struct NewTask {
+    pub name: String,
+    pub project: Option<String>,
+    /// Expected hours; defaults to the template's estimate, or 1.0.
+    pub hours: Option<f64>,
+    /// Due date (YYYY-MM-DD), optionally with a time (YYYY-MM-DD HH:MM).
+    pub due: String,
+    /// Day work should start (YYYY-MM-DD).
+    pub scheduled: Option<String>,
+    /// Day the task becomes pending (YYYY-MM-DD); until then it is waiting and hidden.
+    pub wait: Option<String>,
+    pub template: Option<String>,
+    pub recur: Option<String>,
+    /// Tags, with or without a leading `+`.
+    pub tags: Vec<String>,
+    /// Priority; defaults to the template's priority, or none.
+    pub priority: Option<Priority>,
+    /// Checklist steps, added after the template's steps.
+    pub checklist: Vec<String>,
+    pub description: Option<String>,
+    /// User-defined attribute values, set after the template's defaults.
+    pub udas: Vec<(String, String)>,
+}
+
+/// Changes to an existing task, see [`cmd_edit`]. `None` and empty fields are left unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct TaskEdit {
+    pub name: Option<String>,
+    pub project: Option<String>,
+    pub template: Option<String>,
+    pub hours: Option<f64>,
+    /// New due date (YYYY-MM-DD), optionally with a time (YYYY-MM-DD HH:MM).
+    pub due: Option<String>,
+    /// New scheduled date (YYYY-MM-DD); an empty string clears it.
+    pub scheduled: Option<String>,
+    /// New recurrence pattern; an empty string clears it.
+    pub recur: Option<String>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub priority: Option<Priority>,
+    /// IDs of the tasks this one waits on, replacing the current ones.
+    pub depends_on: Option<Vec<u64>>,
+    /// New description; an empty string clears it.
+    pub description: Option<String>,
+    /// New status: pending, active or waiting. Use [`cmd_complete`] or [`cmd_cancel`] to close a task.
+    pub status: Option<Status>,
+    /// User-defined attribute values to set; an empty value removes the attribute.
+    pub udas: Vec<(String, String)>,
+}
+
+/// Which tasks `list` and the TUI show, based on their tags and status.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskFilter {
+    /// Tasks must have all of these tags.
+    pub tags: Vec<String>,
+    /// Tasks must have none of these tags.
+    pub not_tags: Vec<String>,
+    /// Tasks must have one of these statuses; any status if empty.
+    pub statuses: Vec<Status>,
+    /// Tasks must have these user-defined attribute values; an empty value requires the
+    /// attribute to be unset.
+    pub udas: Vec<(String, String)>,
+}
+
+impl TaskFilter {
+    /// Builds a filter, normalizing the tag names.
+    pub fn new(tags: Vec<String>, not_tags: Vec<String>) -> Result<TaskFilter> {
+        Ok(TaskFilter { tags: normalize_tags(tags)?, not_tags: normalize_tags(not_tags)?, ..TaskFilter::default() })
+    }
+
+    /// Parses a filter expression such as `+review -blocked status:waiting severity:high`.
+    ///
+    /// `status:` words allow a status; other `name:value` words require a value of a
+    /// user-defined attribute declared in `config`; words starting with `-` exclude a tag;
+    /// all other words require it.
+    pub fn parse(expr: &str, config: &Config) -> Result<TaskFilter> {
+        let (statuses, words): (Vec<&str>, Vec<&str>) = expr.split_whitespace().partition(|w| w.starts_with("status:"));
+        let (udas, words): (Vec<&str>, Vec<&str>) = words.into_iter().partition(|w| !w.starts_with(['+', '-']) && w.contains(':'));
+        let (not_tags, tags): (Vec<&str>, Vec<&str>) = words.into_iter().partition(|w| w.starts_with('-'));
+        let statuses = statuses.iter().map(|s| s["status:".len()..].parse()).collect::<Result<_>>()?;
+        let udas: Vec<(String, String)> = udas.iter().filter_map(|w| w.split_once(':')).map(|(n, v)| (n.to_string(), v.to_string())).collect();
+        for (name, _) in &udas {
+            config.uda(name)?;
+        }
+        Ok(TaskFilter {
+            statuses,
+            udas,
+            ..TaskFilter::new(
+                tags.into_iter().map(String::from).collect(),
+                not_tags.into_iter().map(|t| t[1..].to_string()).collect(),
+            )?
+        })
+    }
+
+    /// Returns whether the filter has no conditions.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.not_tags.is_empty() && self.statuses.is_empty() && self.udas.is_empty()
+    }
+
+    /// Returns whether `task` passes the filter on `today`.
+    pub fn matches(&self, task: &Task, today: NaiveDate) -> bool {
+        self.tags.iter().all(|t| task.has_tag(t))
+            && !self.not_tags.iter().any(|t| task.has_tag(t))
+            && (self.statuses.is_empty() || self.statuses.contains(&task.status_on(today)))
+            && self.udas.iter().all(|(name, value)| uda_matches(task.udas.get(name), value))
+    }
+
+    /// Returns whether a listing shows `task`. Done and cancelled tasks, and tasks waiting
+    /// until a later date, are hidden unless `show_all` is set or the filter asks for a status.
+    pub fn shows(&self, task: &Task, show_all: bool, today: NaiveDate) -> bool {
+        let hidden = task.is_closed() || task.is_hidden_on(today);
+        (show_all || !self.statuses.is_empty() || !hidden) && self.matches(task, today)
+    }
+}
+
+/// Returns whether a task's attribute value matches `wanted` from a filter. Numbers are
+/// compared as numbers, so `points:3` matches `3.0`; other values ignore case.
+fn uda_matches(value: Option<&String>, wanted: &str) -> bool {
+    match (value, wanted) {
+        (None, w) => w.is_empty(),
+        (Some(v), w) => match (v.parse::<f64>(), w.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => v.eq_ignore_ascii_case(w),
+        },
+    }
+}
+
+impl std::fmt::Display for TaskFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words: Vec<String> = self.tags.iter().map(|t| format!("+{}", t))
+            .chain(self.not_tags.iter().map(|t| format!("-{}", t)))
+            .chain(self.statuses.iter().map(|s| format!("status:{}", s.to_string().to_lowercase())))
+            .chain(self.udas.iter().map(|(name, value)| format!("{}:{}", name, value)))
+            .collect();
+        write!(f, "{}", words.join(" "))
+    }
+}
 
 /// Adds a new task to the database.
 ///
-/// If a `template_name` is provided, it attempts to use defaults from that template.
+/// If a template is provided, it attempts to use defaults from that template.
 /// It also checks past completed tasks of that template to estimate duration intelligently.
-pub fn cmd_add(name: String, project: Option<String>, hours: Option<f64>, due: String, template_name: Option<String>, recur: Option<String>, silent: bool) {
-    let due_date = match parse_date(&due) {
-        Ok(d) => d,
-        Err(e) => {
-            if !silent { eprintln!("{}", e); }
-            return;
-        }
-    };
+pub fn cmd_add(store: &TaskStore, new: NewTask, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Add task '{}'", new.name))?;
+    let (due_date, due_time) = parse_due(&new.due)?;
+    let scheduled = new.scheduled.as_deref().map(parse_date).transpose()?;
+    let wait_until = new.wait.as_deref().map(|d| parse_wait_date(d, store.clock().today())).transpose()?;
+    if let Some(r) = &new.recur {
+        validate_recurrence(r)?;
+    }
 
-    let mut final_project = project;
-    let mut final_hours = hours.unwrap_or(1.0);
+    let mut final_project = new.project;
+    let mut final_hours = new.hours.unwrap_or(1.0);
+    let mut final_priority = new.priority.unwrap_or_default();
+    let mut tags = normalize_tags(new.tags)?;
+    let mut steps = Vec::new();
+    let mut udas = BTreeMap::new();
 
-    if let Some(t_name) = &template_name {
-        if let Some(tmpl) = load_template(t_name) {
+    if let Some(t_name) = &new.template {
+        if let Some(tmpl) = tx.load_template(t_name)? {
             if final_project.is_none() {
                 final_project = tmpl.project.clone();
             }
-            if hours.is_none() {
+            if new.hours.is_none() {
                 final_hours = tmpl.default_hours;
             }
+            if new.priority.is_none() {
+                final_priority = tmpl.priority;
+            }
+            for tag in tmpl.tags {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            steps = tmpl.checklist;
+            udas = tmpl.udas;
         } else {
-            create_template_if_missing(t_name, &final_project, final_hours, silent);
+            create_template_if_missing(&tx, t_name, &final_project, final_hours, silent)?;
         }
     }
 
-    modify_tasks(silent, |tasks| {
+    steps.extend(new.checklist);
+    tx.load_config()?.assign(&mut udas, &new.udas)?;
+
+    modify_tasks(&tx, silent, |tasks| {
         let next_id = tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
         let t = Task {
             id: next_id,
-            name,
+            uuid: Uuid::new_v4(),
+            name: new.name,
             project: final_project,
             expected_hours: final_hours.max(0.0),
             due_date,
-            created_at: Local::now().to_rfc3339(),
-            completed: false,
-            hours_worked: 0.0,
-            template: template_name,
-            recurrence: recur,
+            due_time,
+            scheduled,
+            created_at: store.clock().timestamp(),
+            status: if wait_until.is_some() { Status::Waiting } else { Status::Pending },
+            wait_until,
+            work_log: Vec::new(),
+            template: new.template,
+            recurrence: new.recur,
             completed_at: None,
+            tags,
+            priority: final_priority,
+            depends_on: Vec::new(),
+            checklist: steps.into_iter().map(|text| ChecklistItem { text, done: false }).collect(),
+            description: new.description.filter(|d| !d.trim().is_empty()),
+            annotations: Vec::new(),
+            udas,
         };
         tasks.push(t);
-        Some(format!("Task added (id = {})", next_id))
-    });
+        Ok(format!("Task added (id = {})", next_id))
+    })?;
+    tx.commit()
 }
 
 /// Marks a task as complete by ID.
 ///
 /// If the task is recurring, a new task is created with the next due date.
-pub fn cmd_complete(id: u64, silent: bool) {
+///
+/// If the task has open checklist items and `force` is false, asks for confirmation first,
+/// or fails when `silent`. The store stays locked while asking, so the task cannot change
+/// between the check and completing it.
+pub fn cmd_complete(store: &TaskStore, id: u64, force: bool, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Complete task {}", id))?;
+    let open = tx.load_task(id)?.open_items();
+    if open > 0 && !force {
+        let msg = format!("Task {} has {} open checklist item(s).", id, open);
+        if silent {
+            return Err(TaskustError::InvalidArgument(msg));
+        }
+        print!("{} Complete it anyway? [y/N] ", msg);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    close_task(&tx, id, Status::Done, silent)?;
+    tx.commit()
+}
+
+/// Cancels a task that will not be done.
+///
+/// Cancelled tasks don't count towards their template's average duration. A recurring
+/// task still gets its next occurrence; only this one is dropped.
+pub fn cmd_cancel(store: &TaskStore, id: u64, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Cancel task {}", id))?;
+    close_task(&tx, id, Status::Cancelled, silent)?;
+    tx.commit()
+}
+
+/// Puts a task on hold, optionally until `until` (YYYY-MM-DD), after which it is pending again.
+pub fn cmd_wait(store: &TaskStore, id: u64, until: Option<String>, silent: bool) -> Result<()> {
+    let until = until.as_deref().map(|d| parse_wait_date(d, store.clock().today())).transpose()?;
+    let tx = store.begin(format!("Wait on task {}", id))?;
+    modify_task(&tx, id, silent, |task| {
+        if task.is_closed() {
+            return Err(TaskustError::InvalidArgument(format!("Task {} is already {}.", id, task.status.to_string().to_lowercase())));
+        }
+        task.status = Status::Waiting;
+        task.wait_until = until;
+        Ok(match until {
+            Some(date) => format!("Task {} is waiting until {}.", id, date),
+            None => format!("Task {} is waiting.", id),
+        })
+    })?;
+    tx.commit()
+}
+
+/// Closes task `id` as done or cancelled, creating the next occurrence if it recurs.
+///
+/// Completing a task also updates its template's average duration.
+fn close_task(store: &TaskStore, id: u64, status: Status, silent: bool) -> Result<()> {
     let mut template_to_update: Option<String> = None;
 
-    modify_tasks(silent, |tasks| {
+    modify_tasks(store, silent, |tasks| {
         let mut new_task: Option<Task> = None;
-        
-        if let Some(t) = tasks.iter_mut().find(|t| t.id == id) {
-            t.completed = true;
-            t.completed_at = Some(Local::now().to_rfc3339());
-            if !silent { println!("Task {} marked as complete.", id); }
-
-            if let Some(recur) = &t.recurrence {
-                if let Some(due) = get_next_recurrence(recur, t.due_date) {
-                    new_task = Some(Task {
-                        id: 0, // Placeholder
-                        name: t.name.clone(),
-                        project: t.project.clone(),
-                        expected_hours: t.expected_hours,
-                        due_date: due,
-                        created_at: Local::now().to_rfc3339(),
-                        completed: false,
-                        hours_worked: 0.0,
-                        template: t.template.clone(),
-                        recurrence: t.recurrence.clone(),
-                        completed_at: None,
-                    });
-                    if !silent { println!("Recurring task created due on {}", due); }
-                } else if !silent {
-                    eprintln!("Unknown recurrence pattern '{}'. Supported: daily, weekly, monthly.", recur);
-                }
-            }
+        let mut msg = match status {
+            Status::Cancelled => format!("Task {} cancelled.", id),
+            _ => format!("Task {} marked as complete.", id),
+        };
 
-            if let Some(template) = &t.template {
-                template_to_update = Some(template.clone());
-            }
-        } else {
-            if !silent { eprintln!("Task {} not found.", id); }
-            return None;
+        let t = tasks.iter_mut().find(|t| t.id == id).ok_or(TaskustError::NotFound(id))?;
+        if t.is_closed() {
+            return Err(TaskustError::InvalidArgument(format!("Task {} is already {}.", id, t.status.to_string().to_lowercase())));
+        }
+        if let Some(recur) = &t.recurrence {
+            let due = get_next_recurrence(recur, t.due_date)?;
+            // Scheduled and wait dates keep the same distance from the due date
+            let shift = |date: Option<NaiveDate>| date.map(|d| due - (t.due_date - d));
+            let wait_until = shift(t.wait_until).filter(|d| *d > store.clock().today());
+            new_task = Some(Task {
+                id: 0, // Placeholder
+                uuid: Uuid::new_v4(),
+                name: t.name.clone(),
+                project: t.project.clone(),
+                expected_hours: t.expected_hours,
+                due_date: due,
+                due_time: t.due_time,
+                scheduled: shift(t.scheduled),
+                created_at: store.clock().timestamp(),
+                status: if wait_until.is_some() { Status::Waiting } else { Status::Pending },
+                wait_until,
+                work_log: Vec::new(),
+                template: t.template.clone(),
+                recurrence: t.recurrence.clone(),
+                completed_at: None,
+                tags: t.tags.clone(),
+                priority: t.priority,
+                depends_on: Vec::new(),
+                checklist: t.checklist.iter().map(|i| ChecklistItem { done: false, ..i.clone() }).collect(),
+                description: t.description.clone(),
+                annotations: Vec::new(),
+                udas: t.udas.clone(),
+            });
+            msg.push_str(&format!("\nRecurring task created due on {}", due));
+        }
+        t.status = status;
+        t.wait_until = None;
+        t.completed_at = Some(store.clock().timestamp());
+
+        if status == Status::Done {
+            template_to_update = t.template.clone();
         }
 
         if let Some(mut nt) = new_task {
@@ -103,234 +351,645 @@ pub fn cmd_complete(id: u64, silent: bool) {
             nt.id = next_id;
             tasks.push(nt);
         }
-        
-        // Return empty string to signal save but no extra print (we printed inside)
-        Some(String::new())
-    });
+
+        Ok(msg)
+    })?;
 
     // Update template average duration
     if let Some(tmpl_name) = template_to_update {
-        recalculate_template_average(&tmpl_name, silent);
+        recalculate_template_average(store, &tmpl_name, silent)?;
     }
+    Ok(())
 }
 
 /// Removes a task from the database by ID.
-pub fn cmd_remove(id: u64, silent: bool) {
-    modify_tasks(silent, |tasks| {
+pub fn cmd_remove(store: &TaskStore, id: u64, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Remove task {}", id))?;
+    modify_tasks(&tx, silent, |tasks| {
         let len_before = tasks.len();
         tasks.retain(|t| t.id != id);
         if tasks.len() == len_before {
-            if !silent { eprintln!("Task {} not found.", id); }
-            None
+            Err(TaskustError::NotFound(id))
         } else {
-            Some(format!("Task {} removed.", id))
+            Ok(format!("Task {} removed.", id))
         }
-    });
+    })?;
+    tx.commit()
 }
 
 /// Edits an existing task's details.
-pub fn cmd_edit(
-    id: u64, 
-    name: Option<String>, 
-    project: Option<String>, 
-    template_name: Option<String>,
-    expected_hours: Option<f64>, 
-    hours_worked: Option<f64>, 
-    due: Option<String>, 
-    recur: Option<String>, 
-    silent: bool
-) {
-    modify_task(id, silent, |task| {
-        if let Some(n) = name { task.name = n; }
-        if let Some(p) = project { task.project = Some(p); }
-        if let Some(tmpl) = template_name { 
+///
+/// An empty `recur` string clears the task's recurrence.
+pub fn cmd_edit(store: &TaskStore, id: u64, edit: TaskEdit, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Edit task {}", id))?;
+    let due = edit.due.as_deref().map(parse_due).transpose()?;
+    let scheduled = match edit.scheduled.as_deref() {
+        Some("") => Some(None),
+        Some(d) => Some(Some(parse_date(d)?)),
+        None => None,
+    };
+    if let Some(r) = edit.recur.as_deref().filter(|r| !r.is_empty()) {
+        validate_recurrence(r)?;
+    }
+    let add_tags = normalize_tags(edit.add_tags)?;
+    let remove_tags = normalize_tags(edit.remove_tags)?;
+    let depends_on = edit.depends_on.as_deref().map(|d| check_dependencies(&tx, id, d)).transpose()?;
+    if let Some(status) = edit.status.filter(|s| s.is_closed()) {
+        return Err(TaskustError::InvalidArgument(format!(
+            "Use `complete` or `cancel` to mark a task {}.", status.to_string().to_lowercase()
+        )));
+    }
+    let config = tx.load_config()?;
+
+    let mut new_template: Option<(String, Option<String>, f64)> = None;
+    modify_task(&tx, id, silent, |task| {
+        if let Some(n) = edit.name { task.name = n; }
+        if let Some(p) = edit.project { task.project = Some(p); }
+        if let Some(tmpl) = edit.template {
             task.template = Some(tmpl.clone());
-            create_template_if_missing(&tmpl, &task.project, task.expected_hours, silent);
-        }
-        if let Some(h) = expected_hours { task.expected_hours = h; }
-        if let Some(h) = hours_worked { task.hours_worked = h; }
-        if let Some(r) = recur { task.recurrence = Some(r); }
-        if let Some(d) = due {
-             match parse_date(&d) {
-                Ok(date) => task.due_date = date,
-                Err(e) => {
-                    if !silent { eprintln!("{}", e); }
-                    return None;
-                }
+            new_template = Some((tmpl, task.project.clone(), task.expected_hours));
+        }
+        if let Some(h) = edit.hours { task.expected_hours = h.max(0.0); }
+        if let Some(r) = edit.recur { task.recurrence = Some(r).filter(|r| !r.is_empty()); }
+        if let Some((date, time)) = due {
+            task.due_date = date;
+            task.due_time = time;
+        }
+        if let Some(date) = scheduled { task.scheduled = date; }
+        if let Some(p) = edit.priority { task.priority = p; }
+        if let Some(d) = depends_on { task.depends_on = d; }
+        if let Some(d) = edit.description { task.description = Some(d).filter(|d| !d.trim().is_empty()); }
+        if let Some(status) = edit.status {
+            // Reopens a closed task; only `wait` sets a wait date
+            task.status = status;
+            task.wait_until = None;
+            task.completed_at = None;
+        }
+        task.tags.retain(|t| !remove_tags.contains(t));
+        for tag in add_tags {
+            if !task.has_tag(&tag) {
+                task.tags.push(tag);
             }
         }
-        Some(format!("Task {} updated.", id))
-    });
+        config.assign(&mut task.udas, &edit.udas)?;
+        Ok(format!("Task {} updated.", id))
+    })?;
+
+    if let Some((tmpl, project, hours)) = new_template {
+        create_template_if_missing(&tx, &tmpl, &project, hours, silent)?;
+    }
+    tx.commit()
 }
 
 /// Logs hours worked on a specific task.
-/// 
-/// hours_worked += hours
-pub fn cmd_log(id: u64, hours: f64, silent: bool) {
-    modify_task(id, silent, |task| {
-        task.hours_worked += hours;
-        Some(format!("Logged {:.2} hours to task {}. Total worked: {:.2} hours.", hours, id, task.hours_worked))
-    });
+///
+/// Appends a work entry dated `date` (today if `None`) with an optional note. `hours` must
+/// be positive.
+pub fn cmd_log(store: &TaskStore, id: u64, hours: f64, date: Option<String>, note: Option<String>, silent: bool) -> Result<()> {
+    if hours <= 0.0 || hours.is_nan() {
+        return Err(TaskustError::InvalidArgument(format!("Hours worked must be positive, got {}.", hours)));
+    }
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => store.clock().today(),
+    };
+    let tx = store.begin(format!("Log {:.2} hours to task {}", hours, id))?;
+    log_work(&tx, id, WorkEntry { date, hours, note }, silent)?;
+    tx.commit()
+}
+
+/// Starts a timer on a task, stopping and logging any timer already running on another task.
+///
+/// A pending or waiting task becomes active.
+pub fn cmd_start(store: &TaskStore, id: u64, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Start timer on task {}", id))?;
+    let task = tx.load_task(id)?;
+    if let Some(timer) = tx.load_timer()? {
+        if timer.task_uuid == task.uuid {
+            return Err(TaskustError::InvalidArgument(format!("The timer is already running on task {}.", id)));
+        }
+        stop_timer(&tx, timer, silent)?;
+    }
+    tx.save_timer(&Timer::start(&task, &store.clock()))?;
+    if matches!(task.status, Status::Pending | Status::Waiting) {
+        modify_task(&tx, id, true, |task| {
+            task.status = Status::Active;
+            task.wait_until = None;
+            Ok(String::new())
+        })?;
+    }
+    if !silent {
+        println!("Started timer on task {} ({}).", id, task.name);
+    }
+    tx.commit()
+}
+
+/// Stops the running timer and logs the elapsed time as a work entry on its task.
+pub fn cmd_stop(store: &TaskStore, silent: bool) -> Result<()> {
+    let tx = store.begin("Stop timer")?;
+    let timer = tx.load_timer()?
+        .ok_or_else(|| TaskustError::InvalidArgument("No timer is running.".to_string()))?;
+    stop_timer(&tx, timer, silent)?;
+    tx.commit()
+}
+
+/// Logs the time elapsed on `timer` to its task and clears the timer.
+///
+/// The entry is dated the day the timer was started. The task is looked up by UUID, so
+/// nothing is logged if it was removed or archived while the timer ran.
+fn stop_timer(store: &TaskStore, timer: Timer, silent: bool) -> Result<()> {
+    let clock = store.clock();
+    let elapsed = timer.elapsed(&clock);
+    let Some(id) = timer.task(&store.load_tasks()?).map(|t| t.id) else {
+        if !silent {
+            println!("Stopped timer after {}; its task no longer exists.", format_elapsed(elapsed));
+        }
+        return store.clear_timer();
+    };
+    let entry = WorkEntry {
+        date: timer.started().map(|t| clock.date_of(t.with_timezone(&Utc))).unwrap_or_else(|| clock.today()),
+        hours: elapsed.num_seconds() as f64 / 3600.0,
+        note: None,
+    };
+    if !silent {
+        println!("Stopped timer on task {} after {}.", id, format_elapsed(elapsed));
+    }
+    log_work(store, id, entry, silent)?;
+    store.clear_timer()
+}
+
+/// Appends a work entry to a task. Shared by [`cmd_log`] and the timer.
+fn log_work(store: &TaskStore, id: u64, entry: WorkEntry, silent: bool) -> Result<()> {
+    modify_task(store, id, silent, |task| {
+        let (hours, date) = (entry.hours, entry.date);
+        task.work_log.push(entry);
+        Ok(format!("Logged {:.2} hours to task {} on {}. Total worked: {:.2} hours.", hours, id, date, task.hours_worked()))
+    })
 }
 
 /// Updates the estimated remaining hours for a task.
 ///
 /// expected_hours = hours_worked + remaining
-pub fn cmd_estimate(id: u64, remaining: f64, silent: bool) {
-    modify_task(id, silent, |task| {
-        let new_total = task.hours_worked + remaining;
-        let worked = task.hours_worked;
+pub fn cmd_estimate(store: &TaskStore, id: u64, remaining: f64, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Estimate task {}", id))?;
+    modify_task(&tx, id, silent, |task| {
+        let worked = task.hours_worked();
+        let new_total = worked + remaining;
         task.expected_hours = new_total;
-        Some(format!("Updated task {} estimate. Total expected: {:.2}h (Worked: {:.2}h + Remaining: {:.2}h)", 
+        Ok(format!("Updated task {} estimate. Total expected: {:.2}h (Worked: {:.2}h + Remaining: {:.2}h)",
                 id, new_total, worked, remaining))
-    });
+    })?;
+    tx.commit()
 }
 
 /// Lists tasks in a formatted table, sorted by urgency.
 ///
-/// By default, hides completed tasks unless `all` is true.
-pub fn cmd_list(all: bool) {
-    let mut tasks = load_tasks();
-    if !all {
-        tasks.retain(|t| !t.completed);
+/// By default, hides done, cancelled and waiting tasks unless `all` is true. Only tasks
+/// matching `filter` are shown; it may only name attributes declared in `config.json`.
+/// Tasks are sorted by the [urgency strategy](UrgencyStrategy) named `sort_by`, or the one
+/// set in `config.json`.
+pub fn cmd_list(store: &TaskStore, all: bool, filter: &TaskFilter, sort_by: Option<&str>) -> Result<()> {
+    let config = store.load_config()?;
+    for (name, _) in &filter.udas {
+        config.uda(name)?;
     }
+    let clock = store.clock();
+    let mut tasks = store.load_tasks()?;
+    if let Some(timer) = store.load_timer()? {
+        match timer.task(&tasks) {
+            Some(task) => println!("Timer running on task {} ({}) for {}.", task.id, task.name, format_elapsed(timer.elapsed(&clock))),
+            None => println!("Timer running on a removed task for {}.", format_elapsed(timer.elapsed(&clock))),
+        }
+    }
+    let all_tasks = tasks.clone();
+    tasks.retain(|t| filter.shows(t, all, clock.today()));
     if tasks.is_empty() {
         println!("No tasks found.");
-        return;
+        return Ok(());
     }
-    
+
     // Sort by urgency descending
-    tasks.sort_by(|a, b| compute_urgency(b).partial_cmp(&compute_urgency(a)).unwrap());
+    let strategy = urgency::strategy(sort_by.unwrap_or(&config.urgency.strategy))?;
+    sort_by_urgency(&mut tasks, &all_tasks, strategy.as_ref(), &config.urgency, &clock);
+
+    let columns = config.columns();
+    let mut header = vec![
+        Cell::new("ID").add_attribute(Attribute::Bold),
+        Cell::new("Name").add_attribute(Attribute::Bold),
+        Cell::new("Project").add_attribute(Attribute::Bold),
+        Cell::new("Tags").add_attribute(Attribute::Bold),
+        Cell::new("Pri").add_attribute(Attribute::Bold),
+        Cell::new("Scheduled").add_attribute(Attribute::Bold),
+        Cell::new("Due").add_attribute(Attribute::Bold),
+        Cell::new("Time Left").add_attribute(Attribute::Bold),
+        Cell::new("Worked").add_attribute(Attribute::Bold),
+        Cell::new("Est").add_attribute(Attribute::Bold),
+        Cell::new("Urg").add_attribute(Attribute::Bold),
+        Cell::new("Status").add_attribute(Attribute::Bold),
+    ];
+    header.extend(columns.iter().map(|u| Cell::new(u.label()).add_attribute(Attribute::Bold)));
 
     let mut table = Table::new();
     table
         .load_preset(UTF8_FULL)
         .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(vec![
-            Cell::new("ID").add_attribute(Attribute::Bold),
-            Cell::new("Name").add_attribute(Attribute::Bold),
-            Cell::new("Project").add_attribute(Attribute::Bold),
-            Cell::new("Due").add_attribute(Attribute::Bold),
-            Cell::new("Time Left").add_attribute(Attribute::Bold),
-            Cell::new("Worked").add_attribute(Attribute::Bold),
-            Cell::new("Est").add_attribute(Attribute::Bold),
-            Cell::new("Urg").add_attribute(Attribute::Bold),
-            Cell::new("Status").add_attribute(Attribute::Bold),
-        ]);
-
-    let today = Local::now().date_naive();
+        .set_header(header);
 
     for t in tasks {
-        table.add_row(create_task_row(&t, today));
+        let mut row = create_task_row(&t, &all_tasks, &clock, &config.urgency, strategy.as_ref());
+        row.extend(columns.iter().map(|u| Cell::new(t.udas.get(&u.name).map(String::as_str).unwrap_or_default())));
+        table.add_row(row);
     }
 
     println!("{table}");
+    Ok(())
 }
 
 /// Adds a new task template.
-pub fn cmd_template_add(name: String, project: Option<String>, hours: f64, silent: bool) {
-    modify_templates(silent, |templates| {
+///
+/// Tasks created from the template get its tags in addition to their own, its priority
+/// unless they set one, its checklist steps and its user-defined attribute values.
+pub fn cmd_template_add(store: &TaskStore, mut template: Template, silent: bool) -> Result<()> {
+    template.tags = normalize_tags(template.tags)?;
+    let udas: Vec<(String, String)> = std::mem::take(&mut template.udas).into_iter().collect();
+    store.load_config()?.assign(&mut template.udas, &udas)?;
+    let name = template.name.clone();
+    let tx = store.begin(format!("Add template '{}'", name))?;
+    modify_templates(&tx, silent, |templates| {
         if templates.iter().any(|t| t.name == name) {
-            if !silent { eprintln!("Template '{}' already exists.", name); }
-            return None;
+            return Err(TaskustError::TemplateExists(name));
         }
-        templates.push(Template { name: name.clone(), project, default_hours: hours });
-        Some(format!("Template '{}' added.", name))
-    });
+        templates.push(template);
+        Ok(format!("Template '{}' added.", name))
+    })?;
+    tx.commit()
 }
 
 /// Lists all available templates.
-pub fn cmd_template_list() {
-    let templates = load_templates();
+pub fn cmd_template_list(store: &TaskStore) -> Result<()> {
+    let templates = store.load_templates()?;
     if templates.is_empty() {
         println!("No templates found.");
-        return;
+        return Ok(());
     }
     let mut table = Table::new();
     table.load_preset(UTF8_FULL)
-        .set_header(vec!["Name", "Default Project", "Default Hours"]);
+        .set_header(vec!["Name", "Default Project", "Default Hours", "Tags", "Priority", "Steps"]);
     for t in templates {
         table.add_row(vec![
             t.name,
             t.project.unwrap_or_else(|| "-".into()),
             format!("{:.2}", t.default_hours),
+            format_tags(&t.tags),
+            t.priority.to_string(),
+            t.checklist.len().to_string(),
         ]);
     }
     println!("{table}");
+    Ok(())
 }
 
 /// Removes a template and updates associated tasks.
-pub fn cmd_template_remove(name: String, silent: bool) {
-    let mut removed = false;
-    modify_templates(silent, |templates| {
+pub fn cmd_template_remove(store: &TaskStore, name: String, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Remove template '{}'", name))?;
+    modify_templates(&tx, silent, |templates| {
         let len_before = templates.len();
         templates.retain(|t| t.name != name);
-        
+
         if templates.len() == len_before {
-            if !silent { eprintln!("Template '{}' not found.", name); }
-            None
+            Err(TaskustError::TemplateNotFound(name.clone()))
         } else {
-            removed = true;
-            Some(format!("Template '{}' removed.", name))
-        }
-    });
-
-    if removed {
-        // Update tasks that used this template
-        modify_tasks(true, |tasks| {
-            let mut changed = false;
-            for t in tasks.iter_mut().filter(|t| t.template.as_ref() == Some(&name)) {
-                t.template = None;
-                changed = true;
-            }
-            if changed { Some(String::new()) } else { None }
-        });
-    }
+            Ok(format!("Template '{}' removed.", name))
+        }
+    })?;
+
+    // Update tasks that used this template
+    modify_tasks(&tx, true, |tasks| {
+        for t in tasks.iter_mut().filter(|t| t.template.as_ref() == Some(&name)) {
+            t.template = None;
+        }
+        Ok(String::new())
+    })?;
+    tx.commit()
 }
 
 /// Resets the database by deleting all tasks and templates.
-pub fn cmd_reset(force: bool) {
+pub fn cmd_reset(store: &TaskStore, force: bool) -> Result<()> {
     if !force {
         print!("Are you sure you want to delete all tasks and templates? This cannot be undone. [y/N] ");
-        io::stdout().flush().unwrap();
+        io::stdout().flush()?;
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        io::stdin().read_line(&mut input)?;
         if input.trim().to_lowercase() != "y" {
             println!("Aborted.");
-            return;
+            return Ok(());
         }
     }
 
-    if let Err(e) = delete_database() {
-        eprintln!("Failed to reset database: {}", e);
-    } else {
-        println!("Database reset successfully.");
+    let _lock = store.lock()?;
+    store.delete_database()?;
+    store.clear_journal()?;
+    store.clear_history()?;
+    store.clear_timer()?;
+    println!("Database reset successfully.");
+    Ok(())
+}
+
+/// Restores the tasks database from the `backup`-th rotated backup (`tasks.json.N`).
+pub fn cmd_restore(store: &TaskStore, backup: usize, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Restore backup {}", backup))?;
+    tx.restore_backup(backup)?;
+    if !silent {
+        println!("Restored tasks from backup {}. The previous state is now backup 1.", backup);
     }
+    tx.commit()
+}
+
+/// Reverts the most recent operation recorded in the undo journal.
+///
+/// Tasks, templates, archive moves and timer starts and stops made by the operation are
+/// reverted together, and the operation can be reapplied with [`cmd_redo`].
+pub fn cmd_undo(store: &TaskStore, silent: bool) -> Result<()> {
+    let _lock = store.lock()?;
+    let mut journal = store.load_journal()?;
+    let op = journal.undo.pop().ok_or_else(|| TaskustError::InvalidArgument("Nothing to undo.".to_string()))?;
+    replay(store, &op, true, &format!("Undo: {}", op.description))?;
+    if !silent {
+        println!("Undone: {}.", op.description);
+    }
+    journal.redo.push(op);
+    store.save_journal(&journal)
+}
+
+/// Reapplies the most recently undone operation.
+pub fn cmd_redo(store: &TaskStore, silent: bool) -> Result<()> {
+    let _lock = store.lock()?;
+    let mut journal = store.load_journal()?;
+    let op = journal.redo.pop().ok_or_else(|| TaskustError::InvalidArgument("Nothing to redo.".to_string()))?;
+    replay(store, &op, false, &format!("Redo: {}", op.description))?;
+    if !silent {
+        println!("Redone: {}.", op.description);
+    }
+    journal.undo.push(op);
+    store.save_journal(&journal)
+}
+
+/// Adds a timestamped note to a task.
+pub fn cmd_annotate(store: &TaskStore, id: u64, text: String, silent: bool) -> Result<()> {
+    if text.trim().is_empty() {
+        return Err(TaskustError::InvalidArgument("Annotation text is empty.".to_string()));
+    }
+    let tx = store.begin(format!("Annotate task {}", id))?;
+    modify_task(&tx, id, silent, |task| {
+        task.annotations.push(Annotation { timestamp: store.clock().timestamp(), text });
+        Ok(format!("Task {} annotated.", id))
+    })?;
+    tx.commit()
+}
+
+/// Prints a task's details, including its description and annotations.
+pub fn cmd_show(store: &TaskStore, id: u64) -> Result<()> {
+    let tasks = store.load_tasks()?;
+    let task = tasks.iter().find(|t| t.id == id).ok_or(TaskustError::NotFound(id))?;
+    println!("Task {}: {}", task.id, name_with_progress(task));
+    println!("UUID:      {}", task.uuid);
+    println!("Project:   {}", task.project.as_deref().unwrap_or("-"));
+    if let Some(date) = task.scheduled {
+        println!("Scheduled: {}", date);
+    }
+    println!("Due:       {}", format_due(task));
+    match (task_status(task, &tasks, store.clock().today()), task.wait_until) {
+        ("Waiting", Some(until)) => println!("Status:    Waiting until {}", until),
+        (status, _) => println!("Status:    {}", status),
+    }
+    if !task.tags.is_empty() {
+        println!("Tags:      {}", format_tags(&task.tags));
+    }
+    if task.priority != Priority::None {
+        println!("Priority:  {}", task.priority);
+    }
+    let config = store.load_config()?;
+    if !task.is_closed() {
+        let clock = store.clock();
+        let available = available_hours(task, &config.urgency, &clock);
+        let risk = if is_at_risk(task, &config.urgency, &clock) { " (at risk)" } else { "" };
+        println!("Workload:  {:.1}h left to do, {:.1}h available{}", task.remaining_hours(), available, risk);
+    }
+    for (name, value) in &task.udas {
+        let label = config.uda(name).map(|u| u.label()).unwrap_or(name);
+        println!("{:<11}{}", format!("{}:", label), value);
+    }
+    if let Some(description) = &task.description {
+        println!("\n{}", description);
+    }
+    if !task.annotations.is_empty() {
+        println!("\nAnnotations:");
+        for a in &task.annotations {
+            println!("  {}  {}", format_timestamp(&a.timestamp), a.text);
+        }
+    }
+    Ok(())
+}
+
+/// Prints the terms that make up a task's urgency, as used to sort `list`, and their total.
+///
+/// The urgency is calculated by the [strategy](UrgencyStrategy) set in `config.json`.
+pub fn cmd_explain(store: &TaskStore, id: u64) -> Result<()> {
+    let tasks = store.load_tasks()?;
+    let task = tasks.iter().find(|t| t.id == id).ok_or(TaskustError::NotFound(id))?;
+    let config = store.load_config()?;
+    let strategy = urgency::strategy(&config.urgency.strategy)?;
+    let clock = store.clock();
+    let terms = strategy.explain(task, &tasks, &config.urgency, &clock);
+    let width = terms.iter().map(|t| t.name.len()).max().unwrap_or(0).max("Urgency".len());
+    println!("Task {}: {}", task.id, name_with_progress(task));
+    for term in &terms {
+        println!("  {:<width$}  {:>+8.2}", term.name, term.value, width = width);
+    }
+    println!("  {:<width$}  {:>8.2}", "Urgency", strategy.urgency(task, &tasks, &config.urgency, &clock), width = width);
+    Ok(())
+}
+
+/// Shows every recorded change to a task as a table, oldest first.
+///
+/// History is kept for removed and archived tasks too. `reference` is a short ID or UUID
+/// prefix as for [`find_task`]; archived tasks are searched after the current ones, and a
+/// removed task can still be found by its ID.
+pub fn cmd_history(store: &TaskStore, reference: &str) -> Result<()> {
+    let tasks = store.load_tasks()?;
+    let mut archived = store.load_archived_tasks()?;
+    // A short ID may have been used by several archived tasks; prefer the latest
+    archived.reverse();
+    let entries = match find_task(&tasks, reference).or_else(|_| find_task(&archived, reference)) {
+        Ok(task) => store.load_history(task.id, Some(task.uuid))?,
+        Err(TaskustError::NotFound(id)) => store.load_history(id, None)?,
+        Err(e) => return Err(e),
+    };
+    if entries.is_empty() {
+        println!("No history for task {}.", reference);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Time", "Change", "Field", "Old", "New"]);
+    for e in entries {
+        table.add_row(vec![
+            format_timestamp(&e.timestamp),
+            e.operation,
+            e.field,
+            format_value(&e.old),
+            format_value(&e.new),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// Appends a checklist item to a task.
+pub fn cmd_item_add(store: &TaskStore, id: u64, text: String, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Add checklist item to task {}", id))?;
+    modify_task(&tx, id, silent, |task| {
+        task.checklist.push(ChecklistItem { text, done: false });
+        Ok(format!("Added item {} to task {}.", task.checklist.len(), id))
+    })?;
+    tx.commit()
+}
+
+/// Marks checklist item `n` (1-based) of a task as done, or as open again if `done` is false.
+pub fn cmd_item_check(store: &TaskStore, id: u64, n: usize, done: bool, silent: bool) -> Result<()> {
+    let verb = if done { "Check" } else { "Uncheck" };
+    let tx = store.begin(format!("{} item {} of task {}", verb, n, id))?;
+    modify_task(&tx, id, silent, |task| {
+        checklist_item(task, n)?.done = done;
+        Ok(format!("Task {}: {} items done.", id, task.progress().unwrap_or_default()))
+    })?;
+    tx.commit()
+}
+
+/// Removes checklist item `n` (1-based) from a task.
+pub fn cmd_item_remove(store: &TaskStore, id: u64, n: usize, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Remove item {} of task {}", n, id))?;
+    modify_task(&tx, id, silent, |task| {
+        checklist_item(task, n)?;
+        let item = task.checklist.remove(n - 1);
+        Ok(format!("Removed '{}' from task {}.", item.text, id))
+    })?;
+    tx.commit()
+}
+
+/// Prints a task's checklist with 1-based item numbers.
+pub fn cmd_item_list(store: &TaskStore, id: u64) -> Result<()> {
+    let task = store.load_task(id)?;
+    if task.checklist.is_empty() {
+        println!("Task {} has no checklist.", id);
+        return Ok(());
+    }
+    println!("{} {} [{}]", task.id, task.name, task.progress().unwrap_or_default());
+    for (i, item) in task.checklist.iter().enumerate() {
+        println!("{:>3}. [{}] {}", i + 1, if item.done { "x" } else { " " }, item.text);
+    }
+    Ok(())
+}
+
+/// Returns checklist item `n` (1-based) of `task`.
+fn checklist_item(task: &mut Task, n: usize) -> Result<&mut ChecklistItem> {
+    let len = task.checklist.len();
+    n.checked_sub(1)
+        .and_then(|i| task.checklist.get_mut(i))
+        .ok_or_else(|| TaskustError::InvalidArgument(format!("Task {} has no item {} (it has {}).", task.id, n, len)))
+}
+
+/// Shows the tasks `id` depends on as a tree, followed by the open tasks waiting on it.
+pub fn cmd_deps(store: &TaskStore, id: u64) -> Result<()> {
+    let tasks = store.load_tasks()?;
+    let task = tasks.iter().find(|t| t.id == id).ok_or(TaskustError::NotFound(id))?;
+    let today = store.clock().today();
+    for line in deps::format_tree(task, &tasks, |t| task_status(t, &tasks, today).to_string()) {
+        println!("{}", line);
+    }
+    let blocked = deps::blocking(task, &tasks);
+    if !blocked.is_empty() {
+        let names: Vec<String> = blocked.iter().map(|t| format!("{} {}", t.id, t.name)).collect();
+        println!("Blocks: {}", names.join(", "));
+    }
+    Ok(())
+}
+
+/// Converts the store to another storage backend.
+///
+/// All tasks, templates and archived tasks are copied unchanged, after which the old
+/// backend's data is removed so the new backend is detected on the next run. The new
+/// backend always uses the default file name in the data directory, which
+/// [`TaskStore::open`] falls back to when the store was opened from a file that is now gone.
+pub fn cmd_migrate(store: &TaskStore, to: Backend, silent: bool) -> Result<()> {
+    let _lock = store.lock()?;
+    if store.backend() == to {
+        return Err(TaskustError::InvalidArgument(format!("The store already uses the {} backend.", to)));
+    }
+
+    let target = TaskStore::open_backend(store.dir(), to)?;
+    if !target.load_tasks()?.is_empty()
+        || !target.load_templates()?.is_empty()
+        || !target.load_archived_tasks()?.is_empty()
+    {
+        return Err(TaskustError::InvalidArgument(format!(
+            "The {} backend in {} already contains data.", to, store.dir().display()
+        )));
+    }
+
+    let tasks = store.load_tasks()?;
+    let templates = store.load_templates()?;
+    let archive = store.load_archived_tasks()?;
+    target.save_tasks(&tasks)?;
+    target.save_templates(&templates)?;
+    if !archive.is_empty() {
+        target.append_to_archive(archive.clone())?;
+    }
+    store.storage().destroy()?;
+
+    if !silent {
+        println!("Migrated {} tasks, {} templates and {} archived tasks from {} to {}.",
+            tasks.len(), templates.len(), archive.len(), store.backend(), to);
+    }
+    Ok(())
 }
 
 /// Edits an existing template.
-pub fn cmd_template_edit(name: String, project: Option<String>, hours: Option<f64>, silent: bool) {
-    modify_template(&name, silent, |t| {
+///
+/// `udas` sets user-defined attribute defaults; an empty value removes one.
+pub fn cmd_template_edit(store: &TaskStore, name: String, project: Option<String>, hours: Option<f64>, priority: Option<Priority>, udas: Vec<(String, String)>, silent: bool) -> Result<()> {
+    let tx = store.begin(format!("Edit template '{}'", name))?;
+    let config = tx.load_config()?;
+    modify_template(&tx, &name, silent, |t| {
+        config.assign(&mut t.udas, &udas)?;
         if let Some(p) = project {
             t.project = Some(p);
         }
         if let Some(h) = hours {
             t.default_hours = h;
         }
-        Some(format!("Template '{}' updated.", name))
-    });
+        if let Some(p) = priority {
+            t.priority = p;
+        }
+        Ok(format!("Template '{}' updated.", name))
+    })?;
+    tx.commit()
 }
 
-/// Archives completed tasks.
+/// Archives done and cancelled tasks.
 ///
-/// If `days` is provided, archives tasks completed more than `days` ago.
-/// Otherwise, archives all completed tasks.
-pub fn cmd_archive(days: Option<i64>, silent: bool) {
-    let tasks = load_tasks();
-    let now = Local::now();
-    
+/// If `days` is provided, archives tasks closed more than `days` ago.
+/// Otherwise, archives all closed tasks.
+pub fn cmd_archive(store: &TaskStore, days: Option<i64>, silent: bool) -> Result<()> {
+    let mut tx = store.begin("Archive completed tasks")?;
+    let tasks = tx.load_tasks()?;
+    let now = store.clock().now_utc();
+
     let tasks_to_archive: Vec<Task> = tasks.into_iter().filter(|t| {
-        if !t.completed { return false; }
+        if !t.is_closed() { return false; }
         if let Some(d) = days {
             if let Some(completed_at_str) = &t.completed_at {
                 if let Ok(completed_at) = chrono::DateTime::parse_from_rfc3339(completed_at_str) {
@@ -346,157 +1005,359 @@ pub fn cmd_archive(days: Option<i64>, silent: bool) {
 
     if tasks_to_archive.is_empty() {
         if !silent { println!("No tasks found to archive."); }
-        return;
+        return Ok(());
     }
 
-    if let Err(e) = append_to_archive(tasks_to_archive.clone()) {
-        if !silent { eprintln!("Failed to archive tasks: {}", e); }
-        return;
-    }
+    tx.append_to_archive(tasks_to_archive.clone())?;
 
     let archived_ids: Vec<u64> = tasks_to_archive.iter().map(|t| t.id).collect();
-    
-    modify_tasks(silent, |tasks| {
+
+    modify_tasks(&tx, silent, |tasks| {
         let len_before = tasks.len();
         tasks.retain(|t| !archived_ids.contains(&t.id));
         let removed = len_before - tasks.len();
-        Some(format!("Archived {} tasks.", removed))
-    });
+        Ok(format!("Archived {} tasks.", removed))
+    })?;
+    tx.commit()
 }
 
-fn modify_task<F>(id: u64, silent: bool, f: F)
+/// Reverts (`undo`) or reapplies an operation, saving only what it touches.
+///
+/// The resulting task changes are added to the history under `description`.
+fn replay(store: &TaskStore, op: &Operation, undo: bool, description: &str) -> Result<()> {
+    let original_tasks = store.load_tasks()?;
+    let original_templates = store.load_templates()?;
+    let mut tasks = original_tasks.clone();
+    let mut templates = original_templates.clone();
+    let mut changes = Vec::new();
+
+    if undo {
+        let unarchived = op.revert(&mut tasks, &mut templates);
+        if unarchived > 0 {
+            store.pop_archive(unarchived)?;
+        }
+    } else {
+        let archived = op.apply(&mut tasks, &mut templates);
+        if !archived.is_empty() {
+            store.append_to_archive(archived.clone())?;
+            changes.push(Change::Archive { tasks: archived });
+        }
+    }
+
+    if tasks != original_tasks {
+        store.save_tasks(&tasks)?;
+    }
+    if templates != original_templates {
+        store.save_templates(&templates)?;
+    }
+    match op.timer(undo) {
+        Some(Some(timer)) => store.save_timer(&timer)?,
+        Some(None) => store.clear_timer()?,
+        None => {}
+    }
+    changes.extend(diff_tasks(&original_tasks, &tasks));
+    store.append_history(&history::entries(&changes, description, &store.clock().timestamp()))
+}
+
+/// Loads a task, applies `f` and saves it, printing the returned message unless `silent`.
+///
+/// Nothing is saved if `f` returns an error.
+fn modify_task<F>(store: &TaskStore, id: u64, silent: bool, f: F) -> Result<()>
 where
-    F: FnOnce(&mut Task) -> Option<String>,
+    F: FnOnce(&mut Task) -> Result<String>,
 {
-    let mut t = load_task(id);
-    match t {
-        Some(ref mut task) => {
-            if let Some(msg) = f(task) {
-                if let Err(e) = save_task(task) {
-                    if !silent { eprintln!("Failed to save task: {}", e); }
-                } else {
-                    if !silent { println!("{}", msg); }
-                }
-            }
-        },
-        None => {
-            if !silent { eprintln!("Task {} not found.", id); }
-        }
+    let mut task = store.load_task(id)?;
+    let msg = f(&mut task)?;
+    store.save_task(&task)?;
+    if !msg.is_empty() && !silent {
+        println!("{}", msg);
     }
+    Ok(())
 }
 
-fn modify_template<F>(name: &str, silent: bool, f: F)
+fn modify_template<F>(store: &TaskStore, name: &str, silent: bool, f: F) -> Result<()>
 where
-    F: FnOnce(&mut Template) -> Option<String>,
+    F: FnOnce(&mut Template) -> Result<String>,
 {
-    let mut templates = load_templates();
-    if let Some(t) = templates.iter_mut().find(|t| t.name == name) {
-        if let Some(msg) = f(t) {
-            if let Err(e) = save_templates(&templates) {
-                if !silent { eprintln!("Failed to save templates: {}", e); }
-            } else {
-                if !silent { println!("{}", msg); }
-            }
-        }
-    } else {
-        if !silent { eprintln!("Template '{}' not found.", name); }
+    let mut templates = store.load_templates()?;
+    let t = templates.iter_mut()
+        .find(|t| t.name == name)
+        .ok_or_else(|| TaskustError::TemplateNotFound(name.to_string()))?;
+    let msg = f(t)?;
+    store.save_templates(&templates)?;
+    if !msg.is_empty() && !silent {
+        println!("{}", msg);
     }
+    Ok(())
 }
 
-fn modify_tasks<F>(silent: bool, f: F)
+fn modify_tasks<F>(store: &TaskStore, silent: bool, f: F) -> Result<()>
 where
-    F: FnOnce(&mut Vec<Task>) -> Option<String>,
+    F: FnOnce(&mut Vec<Task>) -> Result<String>,
 {
-    let mut tasks = load_tasks();
-    if let Some(msg) = f(&mut tasks) {
-        if let Err(e) = save_tasks(&tasks) {
-            if !silent { eprintln!("Failed to save tasks: {}", e); }
-        } else if !msg.is_empty() {
-            if !silent { println!("{}", msg); }
-        }
+    let mut tasks = store.load_tasks()?;
+    let msg = f(&mut tasks)?;
+    store.save_tasks(&tasks)?;
+    if !msg.is_empty() && !silent {
+        println!("{}", msg);
     }
+    Ok(())
 }
 
-fn modify_templates<F>(silent: bool, f: F)
+fn modify_templates<F>(store: &TaskStore, silent: bool, f: F) -> Result<()>
 where
-    F: FnOnce(&mut Vec<Template>) -> Option<String>,
+    F: FnOnce(&mut Vec<Template>) -> Result<String>,
 {
-    let mut templates = load_templates();
-    if let Some(msg) = f(&mut templates) {
-        if let Err(e) = save_templates(&templates) {
-            if !silent { eprintln!("Failed to save templates: {}", e); }
-        } else if !msg.is_empty() {
-            if !silent { println!("{}", msg); }
-        }
+    let mut templates = store.load_templates()?;
+    let msg = f(&mut templates)?;
+    store.save_templates(&templates)?;
+    if !msg.is_empty() && !silent {
+        println!("{}", msg);
     }
+    Ok(())
 }
 
-fn get_next_recurrence(recur: &str, current: NaiveDate) -> Option<NaiveDate> {
+fn get_next_recurrence(recur: &str, current: NaiveDate) -> Result<NaiveDate> {
     match recur.to_lowercase().as_str() {
-        "daily" => Some(current + Duration::days(1)),
-        "weekly" => Some(current + Duration::weeks(1)),
-        "monthly" => Some(current + Duration::days(30)),
-        _ => None,
+        "daily" => Ok(current + Duration::days(1)),
+        "weekly" => Ok(current + Duration::weeks(1)),
+        "monthly" => Ok(current + Duration::days(30)),
+        _ => Err(TaskustError::UnknownRecurrence(recur.to_string())),
     }
 }
 
-fn recalculate_template_average(tmpl_name: &str, silent: bool) {
-    let tasks = load_tasks();
+fn validate_recurrence(recur: &str) -> Result<()> {
+    get_next_recurrence(recur, NaiveDate::MIN).map(|_| ())
+}
+
+fn recalculate_template_average(store: &TaskStore, tmpl_name: &str, silent: bool) -> Result<()> {
+    let tasks = store.load_tasks()?;
     let completed_with_template: Vec<&Task> = tasks.iter()
-        .filter(|t| t.completed && t.template.as_deref() == Some(tmpl_name))
+        .filter(|t| t.status == Status::Done && t.template.as_deref() == Some(tmpl_name))
         .collect();
-    
-    if !completed_with_template.is_empty() {
-        let total_worked: f64 = completed_with_template.iter().map(|t| t.hours_worked).sum();
-        let avg = total_worked / completed_with_template.len() as f64;
-        
-        modify_template(tmpl_name, silent, |tmpl| {
-            if !silent { 
-                println!("Updating template '{}' average duration to {:.2}h (based on {} tasks)", 
-                    tmpl_name, avg, completed_with_template.len()); 
-            }
-            tmpl.default_hours = avg;
-            Some(String::new())
-        });
+
+    if completed_with_template.is_empty() {
+        return Ok(());
     }
+    let total_worked: f64 = completed_with_template.iter().map(|t| t.hours_worked()).sum();
+    let avg = total_worked / completed_with_template.len() as f64;
+
+    modify_template(store, tmpl_name, silent, |tmpl| {
+        tmpl.default_hours = avg;
+        Ok(format!("Updating template '{}' average duration to {:.2}h (based on {} tasks)",
+            tmpl_name, avg, completed_with_template.len()))
+    })
 }
 
-fn parse_date(date_str: &str) -> Result<NaiveDate, String> {
+/// Parses a date, `YYYY-MM-DD`.
+pub fn parse_date(date_str: &str) -> Result<NaiveDate> {
     NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-        .map_err(|e| format!("Invalid due date '{}': {}. Use YYYY-MM-DD.", date_str, e))
+        .map_err(|_| TaskustError::InvalidDate(date_str.to_string()))
 }
 
-fn create_task_row(t: &Task, today: NaiveDate) -> Vec<Cell> {
-    let urgency = compute_urgency(t);
-    let days_left = (t.due_date - today).num_days();
-    let time_left_str = if days_left < 0 {
-        format!("{}d overdue", days_left.abs())
-    } else if days_left == 0 {
-        "Today".to_string()
-    } else {
-        format!("{}d", days_left)
-    };
+/// Parses a due date with an optional time, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM`.
+pub fn parse_due(due_str: &str) -> Result<(NaiveDate, Option<NaiveTime>)> {
+    let due_str = due_str.trim();
+    match due_str.split_once([' ', 'T']) {
+        Some((date, time)) => {
+            let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| TaskustError::InvalidDate(due_str.to_string()))?;
+            Ok((parse_date(date)?, Some(time)))
+        }
+        None => Ok((parse_date(due_str)?, None)),
+    }
+}
 
-    let urgency_color = if t.completed {
-        Color::Grey
-    } else if urgency > 50.0 {
-        Color::Red
-    } else if urgency > 20.0 {
-        Color::Yellow
+/// Parses a wait date, which must be after `today`.
+fn parse_wait_date(date_str: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let date = parse_date(date_str)?;
+    if date <= today {
+        return Err(TaskustError::InvalidArgument(format!("Wait date {} is not in the future.", date)));
+    }
+    Ok(date)
+}
+
+/// Strips a leading `+` from each tag, drops duplicates and rejects malformed tags.
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let t = tag.trim().trim_start_matches('+');
+        if t.is_empty() || t.contains(|c: char| c.is_whitespace() || c == ',') {
+            return Err(TaskustError::InvalidArgument(format!("Invalid tag '{}'. Tags cannot be empty or contain spaces or commas.", tag)));
+        }
+        if !normalized.iter().any(|n| n == t) {
+            normalized.push(t.to_string());
+        }
+    }
+    Ok(normalized)
+}
+
+/// Formats an optional scheduled date for the task table; none is left blank.
+pub fn format_scheduled(date: Option<NaiveDate>) -> String {
+    date.map(|d| d.to_string()).unwrap_or_default()
+}
+
+/// Formats a task's due date for the task table, with its time if it has one.
+pub fn format_due(task: &Task) -> String {
+    match task.due_time {
+        Some(time) => format!("{} {}", task.due_date, time.format("%H:%M")),
+        None => task.due_date.to_string(),
+    }
+}
+
+/// Formats the time left until a task is due, e.g. `5d`, `3h left`, `45m left` or
+/// `2d overdue`. Within a day of the deadline it counts hours rather than days.
+pub fn format_time_left(task: &Task, clock: &Clock) -> String {
+    let hours = clock.hours_left(task);
+    let suffix = if hours < 0.0 { "overdue" } else { "left" };
+    if hours.abs() < 1.0 {
+        format!("{}m {}", (hours.abs() * 60.0) as i64, suffix)
+    } else if hours.abs() < 24.0 {
+        format!("{}h {}", hours.abs() as i64, suffix)
     } else {
-        Color::Green
+        let days = (task.due_date - clock.today()).num_days();
+        if days < 0 { format!("{}d overdue", -days) } else { format!("{}d", days) }
+    }
+}
+
+/// Formats the time left as [`format_time_left`] does, flagging a task that is
+/// [at risk](is_at_risk), e.g. `3d at risk`.
+pub fn format_time_left_at_risk(task: &Task, clock: &Clock, config: &UrgencyConfig) -> String {
+    let left = format_time_left(task, clock);
+    if is_at_risk(task, config, clock) { format!("{} at risk", left) } else { left }
+}
+
+/// Formats tags for display, e.g. `+review +oncall`.
+pub fn format_tags(tags: &[String]) -> String {
+    tags.iter().map(|t| format!("+{}", t)).collect::<Vec<_>>().join(" ")
+}
+
+/// Formats a priority for the task table; no priority is left blank.
+pub fn format_priority(priority: Priority) -> String {
+    match priority {
+        Priority::None => String::new(),
+        p => p.to_string(),
+    }
+}
+
+fn priority_color(priority: Priority) -> Color {
+    match priority {
+        Priority::High => Color::Red,
+        Priority::Medium => Color::Yellow,
+        Priority::Low => Color::Blue,
+        Priority::None => Color::Reset,
+    }
+}
+
+/// Returns the task's name followed by its checklist progress, e.g. `Release [3/8]`.
+pub fn name_with_progress(task: &Task) -> String {
+    match task.progress() {
+        Some(p) => format!("{} [{}]", task.name, p),
+        None => task.name.clone(),
+    }
+}
+
+/// Returns the status shown for a task on `today`: its [`Status`], or "Blocked" for an open
+/// task waiting on a dependency.
+pub fn task_status(task: &Task, tasks: &[Task], today: NaiveDate) -> &'static str {
+    match task.status_on(today) {
+        Status::Pending | Status::Active if is_blocked(task, tasks) => "Blocked",
+        Status::Pending => "Pending",
+        Status::Active => "Active",
+        Status::Waiting => "Waiting",
+        Status::Done => "Done",
+        Status::Cancelled => "Cancelled",
+    }
+}
+
+/// Finds the task `reference` points to: a short ID such as `12`, or the start of a UUID
+/// such as `3f2a9c`.
+///
+/// A reference made only of digits is always read as a short ID, so an ID that is no longer
+/// in use can't match an unrelated UUID.
+pub fn find_task<'a>(tasks: &'a [Task], reference: &str) -> Result<&'a Task> {
+    let reference = reference.trim();
+    if let Ok(id) = reference.parse::<u64>() {
+        return tasks.iter().find(|t| t.id == id).ok_or(TaskustError::NotFound(id));
+    }
+    let prefix = reference.to_lowercase();
+    let matches: Vec<&Task> = tasks.iter().filter(|t| t.uuid.to_string().starts_with(&prefix)).collect();
+    match matches.as_slice() {
+        [task] => Ok(task),
+        [] => Err(TaskustError::UuidNotFound(reference.to_string())),
+        _ => {
+            let ids: Vec<String> = matches.iter().map(|t| t.id.to_string()).collect();
+            Err(TaskustError::InvalidArgument(format!(
+                "UUID prefix '{}' matches tasks {}; use more of it.", reference, ids.join(", ")
+            )))
+        }
+    }
+}
+
+/// Returns the short ID of the current task `reference` points to, see [`find_task`].
+pub fn resolve_id(store: &TaskStore, reference: &str) -> Result<u64> {
+    Ok(find_task(&store.load_tasks()?, reference)?.id)
+}
+
+/// Parses a comma-separated list of task IDs such as `3,7`. An empty string is an empty list.
+pub fn parse_ids(s: &str) -> Result<Vec<u64>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(|_| TaskustError::InvalidArgument(format!("Invalid task ID '{}'.", id))))
+        .collect()
+}
+
+/// Checks that task `id` may depend on the tasks with short IDs `depends_on`: every
+/// dependency must exist and the result must not contain a cycle.
+///
+/// Returns the UUIDs of the dependencies, which is how they are stored.
+fn check_dependencies(store: &TaskStore, id: u64, depends_on: &[u64]) -> Result<Vec<Uuid>> {
+    let mut tasks = store.load_tasks()?;
+    let uuids = depends_on
+        .iter()
+        .map(|dep| tasks.iter().find(|t| t.id == *dep).map(|t| t.uuid).ok_or(TaskustError::NotFound(*dep)))
+        .collect::<Result<Vec<_>>>()?;
+    let task = tasks.iter_mut().find(|t| t.id == id).ok_or(TaskustError::NotFound(id))?;
+    task.depends_on = uuids.clone();
+    match deps::find_cycle(&tasks) {
+        Some(cycle) => Err(TaskustError::DependencyCycle(cycle)),
+        None => Ok(uuids),
+    }
+}
+
+fn create_task_row(t: &Task, tasks: &[Task], clock: &Clock, config: &UrgencyConfig, strategy: &dyn UrgencyStrategy) -> Vec<Cell> {
+    let today = clock.today();
+    let urgency = strategy.urgency(t, tasks, config, clock);
+    let overdue = clock.hours_left(t) < 0.0;
+
+    let urgency_color = match strategy.level(urgency, config) {
+        _ if t.is_closed() => Color::Grey,
+        UrgencyLevel::High => Color::Red,
+        UrgencyLevel::Medium => Color::Yellow,
+        UrgencyLevel::Low => Color::Green,
     };
 
-    let status = if t.completed { "Done" } else { "Pending" };
-    let status_color = if t.completed { Color::Green } else { Color::Yellow };
+    let status = task_status(t, tasks, today);
+    let status_color = match status {
+        "Done" => Color::Green,
+        "Cancelled" => Color::Grey,
+        "Blocked" => Color::Magenta,
+        "Active" => Color::Cyan,
+        "Waiting" => Color::Blue,
+        _ => Color::Yellow,
+    };
 
     vec![
         Cell::new(t.id),
-        Cell::new(&t.name),
+        Cell::new(name_with_progress(t)),
         Cell::new(t.project.as_deref().unwrap_or_default()),
-        Cell::new(t.due_date),
-        Cell::new(time_left_str).fg(if days_left < 0 && !t.completed { Color::Red } else { Color::Reset }),
-        Cell::new(format!("{:.1}", t.hours_worked)),
+        Cell::new(format_tags(&t.tags)).fg(Color::Cyan),
+        Cell::new(format_priority(t.priority)).fg(priority_color(t.priority)),
+        Cell::new(format_scheduled(t.scheduled)).fg(if status == "Pending" && t.scheduled.is_some_and(|d| d <= today) { Color::Yellow } else { Color::Reset }),
+        Cell::new(format_due(t)),
+        Cell::new(format_time_left_at_risk(t, clock, config)).fg(if (overdue && !t.is_closed()) || is_at_risk(t, config, clock) { Color::Red } else { Color::Reset }),
+        Cell::new(format!("{:.1}", t.hours_worked())),
         Cell::new(format!("{:.1}", t.expected_hours)),
         Cell::new(format!("{:.1}", urgency)).fg(urgency_color),
         Cell::new(status).fg(status_color),
@@ -506,17 +1367,18 @@ fn create_task_row(t: &Task, today: NaiveDate) -> Vec<Cell> {
 /// Helper function to create a template if it doesn't exist.
 ///
 /// This is used when adding or editing a task with a template name that is not yet in the database.
-fn create_template_if_missing(name: &str, project: &Option<String>, hours: f64, silent: bool) {
-    if load_template(name).is_none() {
-        if !silent { println!("Template '{}' not found. Creating it.", name); }
-        modify_templates(silent, |templates| {
-            templates.push(Template {
-                name: name.to_string(),
-                project: project.clone(),
-                default_hours: hours,
-            });
-            None
-        });
+fn create_template_if_missing(store: &TaskStore, name: &str, project: &Option<String>, hours: f64, silent: bool) -> Result<()> {
+    if store.load_template(name)?.is_some() {
+        return Ok(());
     }
+    if !silent { println!("Template '{}' not found. Creating it.", name); }
+    modify_templates(store, silent, |templates| {
+        templates.push(Template {
+            name: name.to_string(),
+            project: project.clone(),
+            default_hours: hours,
+            ..Default::default()
+        });
+        Ok(String::new())
+    })
 }
-
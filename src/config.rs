@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, TaskustError};
+use crate::urgency::{self, UrgencyConfig};
+
+/// Settings stored in `config.json` in the data directory. Every setting is optional.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Custom attributes tasks and templates may carry, in column order.
+    pub udas: Vec<Uda>,
+    /// Coefficients of the urgency model.
+    pub urgency: UrgencyConfig,
+}
+
+/// A user-defined attribute (UDA): a named, typed field for data taskust has no field for,
+/// such as story points or a customer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Uda {
+    /// Name used in `--set name=value` and filters such as `name:value`.
+    pub name: String,
+    /// What values the attribute accepts.
+    #[serde(rename = "type")]
+    pub kind: UdaType,
+    /// For an enum, the allowed values.
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// Column header and label; the name if not set.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Whether `list` and the TUI show the attribute as a column.
+    #[serde(default)]
+    pub column: bool,
+}
+
+/// The type of a [`Uda`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UdaType {
+    String,
+    Number,
+    /// A date, `YYYY-MM-DD`.
+    Date,
+    /// One of the attribute's `values`.
+    Enum,
+}
+
+impl Config {
+    /// Loads the configuration from `path`, or the defaults if it does not exist.
+    ///
+    /// UDA declarations and urgency coefficients are checked here, so a typo in the file is reported up front
+    /// rather than when a task is edited.
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let s = fs::read_to_string(path)?;
+        let config: Config = serde_json::from_str(&s).map_err(|e| TaskustError::Parse {
+            file: path.to_path_buf(),
+            line: e.line(),
+            message: e.to_string(),
+        })?;
+        config.validate().map_err(|message| TaskustError::Parse { file: path.to_path_buf(), line: 0, message })?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        for (i, uda) in self.udas.iter().enumerate() {
+            if uda.name.is_empty() || uda.name == "status" || uda.name.contains(|c: char| c.is_whitespace() || c == ':' || c == '=') {
+                return Err(format!("invalid attribute name '{}'", uda.name));
+            }
+            if self.udas[..i].iter().any(|u| u.name == uda.name) {
+                return Err(format!("attribute '{}' is declared twice", uda.name));
+            }
+            if uda.kind == UdaType::Enum && uda.values.is_empty() {
+                return Err(format!("enum attribute '{}' has no values", uda.name));
+            }
+        }
+        if self.urgency.effort_hours <= 0.0 {
+            return Err("urgency.effort_hours must be positive".to_string());
+        }
+        if !(self.urgency.hours_per_day > 0.0 && self.urgency.hours_per_day <= 24.0) {
+            return Err("urgency.hours_per_day must be between 0 and 24".to_string());
+        }
+        if !(1..=7).contains(&self.urgency.days_per_week) {
+            return Err("urgency.days_per_week must be between 1 and 7".to_string());
+        }
+        if self.urgency.scheduled_max_days < 0.0 {
+            return Err("urgency.scheduled_max_days must not be negative".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.urgency.blocked_factor) {
+            return Err("urgency.blocked_factor must be between 0 and 1".to_string());
+        }
+        urgency::strategy(&self.urgency.strategy).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Returns the declaration of attribute `name`.
+    pub fn uda(&self, name: &str) -> Result<&Uda> {
+        self.udas.iter().find(|u| u.name == name).ok_or_else(|| {
+            TaskustError::InvalidArgument(format!("Unknown attribute '{}'. Declare it in config.json first.", name))
+        })
+    }
+
+    /// Returns the attributes shown as columns, in order.
+    pub fn columns(&self) -> Vec<&Uda> {
+        self.udas.iter().filter(|u| u.column).collect()
+    }
+
+    /// Sets attribute values on `values`, as given to `--set` (see [`parse_assignments`]).
+    ///
+    /// Each value is checked against its attribute's type and stored in a canonical form;
+    /// an empty value removes the attribute.
+    pub fn assign(&self, values: &mut BTreeMap<String, String>, assignments: &[(String, String)]) -> Result<()> {
+        for (name, value) in assignments {
+            let uda = self.uda(name)?;
+            match value.as_str() {
+                "" => values.remove(&uda.name),
+                value => values.insert(uda.name.clone(), uda.parse_value(value)?),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Splits `name=value` assignments such as `points=3`. The value may be empty.
+pub fn parse_assignments(assignments: &[String]) -> Result<Vec<(String, String)>> {
+    assignments
+        .iter()
+        .map(|a| {
+            a.split_once('=')
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| TaskustError::InvalidArgument(format!("Invalid assignment '{}'. Use name=value.", a)))
+        })
+        .collect()
+}
+
+impl Uda {
+    /// Returns the column header and label for the attribute.
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Checks `value` against the attribute's type and returns it in canonical form.
+    pub fn parse_value(&self, value: &str) -> Result<String> {
+        match self.kind {
+            UdaType::String => Ok(value.to_string()),
+            UdaType::Number => value.parse::<f64>().ok().filter(|n| n.is_finite()).map(|n| n.to_string()).ok_or_else(|| {
+                TaskustError::InvalidArgument(format!("Attribute '{}' must be a number, got '{}'.", self.name, value))
+            }),
+            UdaType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.to_string())
+                .map_err(|_| TaskustError::InvalidDate(value.to_string())),
+            UdaType::Enum => self.values.iter().find(|v| v.eq_ignore_ascii_case(value)).cloned().ok_or_else(|| {
+                TaskustError::InvalidArgument(format!(
+                    "Attribute '{}' must be one of {}, got '{}'.", self.name, self.values.join(", "), value
+                ))
+            }),
+        }
+    }
+}
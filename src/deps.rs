@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use uuid::Uuid;
+use crate::models::Task;
+
+/// Returns whether `task` waits on a dependency that is still open.
+///
+/// Dependencies on tasks that are no longer among `tasks`, because they were archived or
+/// removed, are treated as satisfied.
+pub fn is_blocked(task: &Task, tasks: &[Task]) -> bool {
+    task.depends_on.iter().any(|uuid| tasks.iter().any(|t| t.uuid == *uuid && !t.is_closed()))
+}
+
+/// Returns the open tasks that depend on `task`.
+pub fn blocking<'a>(task: &Task, tasks: &'a [Task]) -> Vec<&'a Task> {
+    tasks.iter().filter(|t| !t.is_closed() && t.depends_on.contains(&task.uuid)).collect()
+}
+
+/// Returns the short IDs of the dependencies of `task` that are still among `tasks`.
+pub fn dependency_ids(task: &Task, tasks: &[Task]) -> Vec<u64> {
+    task.depends_on.iter().filter_map(|uuid| tasks.iter().find(|t| t.uuid == *uuid)).map(|t| t.id).collect()
+}
+
+/// Finds a dependency cycle among `tasks`.
+///
+/// Returns the IDs along the cycle with the first repeated at the end, e.g. `[3, 7, 3]`.
+pub fn find_cycle(tasks: &[Task]) -> Option<Vec<u64>> {
+    let mut done: HashSet<Uuid> = HashSet::new();
+    for task in tasks {
+        let mut path = Vec::new();
+        if let Some(cycle) = visit(task, tasks, &mut path, &mut done) {
+            return Some(cycle.iter().map(|t| t.id).collect());
+        }
+    }
+    None
+}
+
+/// Depth-first search from `task`; `path` holds the tasks on the way there.
+fn visit<'a>(task: &'a Task, tasks: &'a [Task], path: &mut Vec<&'a Task>, done: &mut HashSet<Uuid>) -> Option<Vec<&'a Task>> {
+    if let Some(start) = path.iter().position(|p| p.uuid == task.uuid) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(task);
+        return Some(cycle);
+    }
+    if done.contains(&task.uuid) {
+        return None;
+    }
+    path.push(task);
+    for dep in tasks.iter().filter(|t| task.depends_on.contains(&t.uuid)) {
+        if let Some(cycle) = visit(dep, tasks, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(task.uuid);
+    None
+}
+
+/// Renders the dependencies of `task` as an indented tree, one task per line.
+///
+/// Each line shows the task's ID, name and status; `status` supplies the latter.
+/// Dependencies that are no longer among `tasks` are shown by the start of their UUID.
+pub fn format_tree(task: &Task, tasks: &[Task], status: impl Fn(&Task) -> String) -> Vec<String> {
+    let mut lines = vec![format!("{} {} [{}]", task.id, task.name, status(task))];
+    let mut seen = HashSet::from([task.uuid]);
+    push_children(task, tasks, &status, "", &mut seen, &mut lines);
+    lines
+}
+
+fn push_children(task: &Task, tasks: &[Task], status: &impl Fn(&Task) -> String, prefix: &str, seen: &mut HashSet<Uuid>, lines: &mut Vec<String>) {
+    for (i, uuid) in task.depends_on.iter().enumerate() {
+        let last = i == task.depends_on.len() - 1;
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        match tasks.iter().find(|t| t.uuid == *uuid) {
+            Some(dep) if seen.insert(dep.uuid) => {
+                lines.push(format!("{}{}{} {} [{}]", prefix, branch, dep.id, dep.name, status(dep)));
+                push_children(dep, tasks, status, &format!("{}{}", prefix, indent), seen, lines);
+            }
+            Some(dep) => lines.push(format!("{}{}{} {} (see above)", prefix, branch, dep.id, dep.name)),
+            None => lines.push(format!("{}{}{} (archived or removed)", prefix, branch, &uuid.to_string()[..8])),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+use crate::error::{Result, TaskustError};
+
+/// Opens `initial` in the user's editor and returns the saved text.
+///
+/// Uses `$VISUAL`, then `$EDITOR`, falling back to `vi`. The variable may include
+/// arguments, e.g. `code --wait`. Trailing whitespace is trimmed from the result.
+pub fn edit_text(initial: &str) -> Result<String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let path = env::temp_dir().join(format!("taskust-{}.txt", std::process::id()));
+    // End with a newline like a normal text file, so appended lines start on their own line
+    let contents = if initial.is_empty() { String::new() } else { format!("{}\n", initial) };
+    fs::write(&path, contents)?;
+    let status = Command::new(program).args(words).arg(&path).status();
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    let status = status.map_err(|e| {
+        TaskustError::InvalidArgument(format!("Could not run editor '{}': {}", editor, e))
+    })?;
+    if !status.success() {
+        return Err(TaskustError::InvalidArgument(format!("Editor '{}' exited with {}.", editor, status)));
+    }
+    Ok(text?.trim_end().to_string())
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors returned by storage and command functions.
+#[derive(Debug)]
+pub enum TaskustError {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A data file exists but does not contain valid JSON for the expected format.
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// No task with the given ID exists.
+    NotFound(u64),
+    /// No task's UUID starts with the given prefix.
+    UuidNotFound(String),
+    /// No template with the given name exists.
+    TemplateNotFound(String),
+    /// A date string could not be parsed.
+    InvalidDate(String),
+    /// A recurrence pattern is not one of the supported values.
+    UnknownRecurrence(String),
+    /// A template with the given name already exists.
+    TemplateExists(String),
+    /// A command-line argument has an unsupported value.
+    InvalidArgument(String),
+    /// The requested backup file does not exist.
+    BackupNotFound(PathBuf),
+    /// Another process holds the database lock.
+    Locked { pid: Option<u32> },
+    /// The database backend reported an error.
+    Database(String),
+    /// A data file was written by a newer version of taskust.
+    UnsupportedVersion {
+        file: PathBuf,
+        version: u32,
+        supported: u32,
+    },
+    /// Task dependencies would form a cycle; holds the IDs along it.
+    DependencyCycle(Vec<u64>),
+}
+
+/// Convenience alias for results carrying a [`TaskustError`].
+pub type Result<T> = std::result::Result<T, TaskustError>;
+
+impl TaskustError {
+    /// Returns the process exit code used by the CLI for this error.
+    ///
+    /// Each variant maps to a distinct code so scripts can tell failures apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TaskustError::Io(_) => 2,
+            TaskustError::Parse { .. } => 3,
+            TaskustError::NotFound(_) => 4,
+            TaskustError::UuidNotFound(_) => 15,
+            TaskustError::TemplateNotFound(_) => 5,
+            TaskustError::InvalidDate(_) => 6,
+            TaskustError::UnknownRecurrence(_) => 7,
+            TaskustError::TemplateExists(_) => 8,
+            TaskustError::InvalidArgument(_) => 9,
+            TaskustError::BackupNotFound(_) => 10,
+            TaskustError::Locked { .. } => 11,
+            TaskustError::Database(_) => 12,
+            TaskustError::UnsupportedVersion { .. } => 13,
+            TaskustError::DependencyCycle(_) => 14,
+        }
+    }
+}
+
+impl fmt::Display for TaskustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskustError::Io(e) => write!(f, "I/O error: {}", e),
+            TaskustError::Parse { file, line, message } => {
+                write!(f, "Failed to parse {} (line {}): {}", file.display(), line, message)
+            }
+            TaskustError::NotFound(id) => write!(f, "Task {} not found.", id),
+            TaskustError::UuidNotFound(prefix) => write!(f, "No task has a UUID starting with '{}'.", prefix),
+            TaskustError::TemplateNotFound(name) => write!(f, "Template '{}' not found.", name),
+            TaskustError::InvalidDate(date) => {
+                write!(f, "Invalid date '{}'. Use YYYY-MM-DD.", date)
+            }
+            TaskustError::UnknownRecurrence(recur) => write!(
+                f,
+                "Unknown recurrence pattern '{}'. Supported: daily, weekly, monthly.",
+                recur
+            ),
+            TaskustError::TemplateExists(name) => write!(f, "Template '{}' already exists.", name),
+            TaskustError::InvalidArgument(msg) => write!(f, "{}", msg),
+            TaskustError::BackupNotFound(path) => write!(f, "Backup {} not found.", path.display()),
+            TaskustError::Locked { pid: Some(pid) } => write!(f, "Database is locked by pid {}.", pid),
+            TaskustError::Locked { pid: None } => write!(f, "Database is locked by another process."),
+            TaskustError::Database(msg) => write!(f, "Database error: {}", msg),
+            TaskustError::UnsupportedVersion { file, version, supported } => write!(
+                f,
+                "{} uses format version {}, but this build of taskust only supports up to version {}. Please upgrade taskust.",
+                file.display(),
+                version,
+                supported
+            ),
+            TaskustError::DependencyCycle(ids) => {
+                let path: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "Dependency cycle: {}.", path.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaskustError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TaskustError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TaskustError {
+    fn from(e: io::Error) -> Self {
+        TaskustError::Io(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for TaskustError {
+    fn from(e: rusqlite::Error) -> Self {
+        TaskustError::Database(e.to_string())
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::{Result, TaskustError};
+use crate::journal::Change;
+use crate::models::Task;
+use uuid::Uuid;
+
+/// One field of one task changing, as stored in `history.jsonl`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// ID of the task that changed.
+    pub task_id: u64,
+    /// UUID of the task that changed; missing from entries recorded before tasks had one.
+    #[serde(default)]
+    pub task_uuid: Option<Uuid>,
+    /// Timestamp of the change (ISO 8601).
+    pub timestamp: String,
+    /// Description of the operation that made the change, e.g. "Edit task 3".
+    pub operation: String,
+    /// Name of the changed field, or `created`, `removed` or `archived` for the task itself.
+    pub field: String,
+    /// The previous value (`null` if none).
+    pub old: Value,
+    /// The new value (`null` if none).
+    pub new: Value,
+}
+
+/// Builds the history entries for the task changes in `changes`.
+pub fn entries(changes: &[Change], operation: &str, timestamp: &str) -> Vec<HistoryEntry> {
+    let archived: HashSet<u64> = changes
+        .iter()
+        .filter_map(|c| match c {
+            Change::Archive { tasks } => Some(tasks.iter().map(|t| t.id)),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    let entry = |task: &Task, field: &str, old, new| HistoryEntry {
+        task_id: task.id,
+        task_uuid: Some(task.uuid),
+        timestamp: timestamp.to_string(),
+        operation: operation.to_string(),
+        field: field.to_string(),
+        old,
+        new,
+    };
+
+    let mut entries = Vec::new();
+    for change in changes {
+        match change {
+            Change::Task { before: None, after: Some(t) } => {
+                entries.push(entry(t, "created", Value::Null, Value::from(t.name.clone())));
+            }
+            Change::Task { before: Some(t), after: None } => {
+                let field = if archived.contains(&t.id) { "archived" } else { "removed" };
+                entries.push(entry(t, field, Value::from(t.name.clone()), Value::Null));
+            }
+            Change::Task { before: Some(before), after: Some(after) } => {
+                for (field, old, new) in field_changes(before, after) {
+                    entries.push(entry(after, &field, old, new));
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Returns `(field, old, new)` for every field that differs between two versions of a task.
+///
+/// Changes to the work log are reported as the change in total `hours_worked`.
+fn field_changes(before: &Task, after: &Task) -> Vec<(String, Value, Value)> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) = (serde_json::to_value(before), serde_json::to_value(after)) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let o = old.get(field).cloned().unwrap_or(Value::Null);
+            let n = new.get(field).cloned().unwrap_or(Value::Null);
+            match field.as_str() {
+                _ if o == n => None,
+                "work_log" => Some((
+                    "hours_worked".to_string(),
+                    Value::from(before.hours_worked()),
+                    Value::from(after.hours_worked()),
+                )),
+                _ => Some((field.clone(), o, n)),
+            }
+        })
+        .collect()
+}
+
+/// Appends `entries` to the history file at `path`, one JSON object per line.
+pub fn append(path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut out = String::new();
+    for e in entries {
+        out.push_str(&serde_json::to_string(e).map_err(std::io::Error::other)?);
+        out.push('\n');
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    f.write_all(out.as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Loads the history of task `id` from `path`, oldest first.
+///
+/// If `uuid` is given, only entries for that task are returned, leaving out other tasks
+/// that had the same ID before or after it; entries recorded without a UUID are matched by
+/// ID. Returns an empty vector if the file does not exist.
+pub fn load(path: &Path, id: u64, uuid: Option<Uuid>) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let s = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    for (i, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let entry: HistoryEntry = serde_json::from_str(line).map_err(|e| TaskustError::Parse {
+            file: path.to_path_buf(),
+            line: i + 1,
+            message: e.to_string(),
+        })?;
+        let matches = match (uuid, entry.task_uuid) {
+            (Some(uuid), Some(entry_uuid)) => uuid == entry_uuid,
+            _ => entry.task_id == id,
+        };
+        if matches {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Formats a recorded value for display: strings without quotes, `null` as `-`.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Formats a recorded timestamp as local `YYYY-MM-DD HH:MM`.
+pub fn format_timestamp(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
@@ -0,0 +1,269 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, TaskustError};
+use crate::history;
+use crate::models::{Task, Template};
+use crate::storage::schema::CURRENT_VERSION;
+use crate::storage::{StoreLock, TaskStore};
+use crate::timer::Timer;
+
+/// Maximum number of operations kept on the undo stack.
+pub const JOURNAL_LIMIT: usize = 100;
+
+/// A single recorded change to the database.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum Change {
+    /// A task was added (`before` is `None`), removed (`after` is `None`) or modified.
+    Task { before: Option<Task>, after: Option<Task> },
+    /// A template was added, removed or modified.
+    Template { before: Option<Template>, after: Option<Template> },
+    /// Tasks were appended to the archive.
+    Archive { tasks: Vec<Task> },
+    /// The timer was started (`before` is `None`), stopped (`after` is `None`) or moved to
+    /// another task.
+    Timer { before: Option<Timer>, after: Option<Timer> },
+}
+
+/// A user-level action, such as one CLI command, and all the changes it made.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Operation {
+    /// Short description of the action, e.g. "Remove task 12".
+    pub description: String,
+    /// Timestamp when the action was performed (ISO 8601).
+    pub timestamp: String,
+    /// The changes in the order they were made.
+    pub changes: Vec<Change>,
+}
+
+/// The undo and redo stacks, stored in `journal.json` in the data directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Journal {
+    /// Data format version of the recorded tasks, see [`crate::storage::schema`].
+    pub version: u32,
+    /// Operations that can be undone, oldest first.
+    pub undo: Vec<Operation>,
+    /// Undone operations that can be redone, oldest first.
+    pub redo: Vec<Operation>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal { version: CURRENT_VERSION, undo: Vec::new(), redo: Vec::new() }
+    }
+}
+
+impl Journal {
+    /// Loads the journal from `path`.
+    ///
+    /// Returns an empty journal if the file does not exist. Journals written with an older
+    /// data format are discarded rather than upgraded, since they only hold undo history.
+    pub fn load(path: &Path) -> Result<Journal> {
+        if !path.exists() {
+            return Ok(Journal::default());
+        }
+        let s = fs::read_to_string(path)?;
+        let parse_error = |e: serde_json::Error| TaskustError::Parse {
+            file: path.to_path_buf(),
+            line: e.line(),
+            message: e.to_string(),
+        };
+        let value: serde_json::Value = serde_json::from_str(&s).map_err(parse_error)?;
+        let version = value.get("version").and_then(serde_json::Value::as_u64).unwrap_or(0);
+        if version > CURRENT_VERSION as u64 {
+            return Err(TaskustError::UnsupportedVersion {
+                file: path.to_path_buf(),
+                version: u32::try_from(version).unwrap_or(u32::MAX),
+                supported: CURRENT_VERSION,
+            });
+        }
+        if version < CURRENT_VERSION as u64 {
+            return Ok(Journal::default());
+        }
+        serde_json::from_value(value).map_err(parse_error)
+    }
+
+    /// Records a new operation, dropping the oldest beyond [`JOURNAL_LIMIT`] and clearing
+    /// the redo stack.
+    pub fn record(&mut self, op: Operation) {
+        self.undo.push(op);
+        if self.undo.len() > JOURNAL_LIMIT {
+            let excess = self.undo.len() - JOURNAL_LIMIT;
+            self.undo.drain(..excess);
+        }
+        self.redo.clear();
+    }
+}
+
+impl Operation {
+    /// Applies the operation again to `tasks` and `templates`.
+    ///
+    /// Archive changes are returned as the tasks to re-append, since the archive is not
+    /// loaded into memory.
+    pub fn apply(&self, tasks: &mut Vec<Task>, templates: &mut Vec<Template>) -> Vec<Task> {
+        let mut archived = Vec::new();
+        for change in &self.changes {
+            match change {
+                Change::Task { before, after } => replace_task(tasks, before, after),
+                Change::Template { before, after } => replace_template(templates, before, after),
+                Change::Archive { tasks } => archived.extend(tasks.iter().cloned()),
+                Change::Timer { .. } => {}
+            }
+        }
+        archived
+    }
+
+    /// Reverts the operation on `tasks` and `templates`.
+    ///
+    /// Returns how many tasks must be removed from the end of the archive.
+    pub fn revert(&self, tasks: &mut Vec<Task>, templates: &mut Vec<Template>) -> usize {
+        let mut unarchived = 0;
+        for change in self.changes.iter().rev() {
+            match change {
+                Change::Task { before, after } => replace_task(tasks, after, before),
+                Change::Template { before, after } => replace_template(templates, after, before),
+                Change::Archive { tasks } => unarchived += tasks.len(),
+                Change::Timer { .. } => {}
+            }
+        }
+        unarchived
+    }
+
+    /// Returns the timer to leave running after reverting (`undo`) or reapplying the
+    /// operation, or `None` if it did not touch the timer.
+    pub fn timer(&self, undo: bool) -> Option<Option<Timer>> {
+        let mut states = self.changes.iter().filter_map(|c| match c {
+            Change::Timer { before, after } => Some(if undo { before } else { after }),
+            _ => None,
+        });
+        if undo { states.next() } else { states.next_back() }.cloned()
+    }
+}
+
+/// An in-progress mutation of a store, recorded in the journal when committed.
+///
+/// Holds the store lock for its whole lifetime and snapshots tasks, templates and the timer
+/// when it begins; [`Transaction::commit`] diffs them against the saved state and also
+/// appends the task changes to the per-task history. Dereferences to the
+/// [`TaskStore`] so command helpers can use it directly. A transaction dropped without
+/// being committed records nothing.
+pub struct Transaction<'a> {
+    store: &'a TaskStore,
+    _lock: StoreLock,
+    description: String,
+    tasks: Vec<Task>,
+    templates: Vec<Template>,
+    timer: Option<Timer>,
+    archived: Vec<Task>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Locks `store` and snapshots its tasks, templates and timer.
+    pub fn begin(store: &'a TaskStore, description: impl Into<String>) -> Result<Transaction<'a>> {
+        let lock = store.lock()?;
+        Ok(Transaction {
+            store,
+            _lock: lock,
+            description: description.into(),
+            tasks: store.load_tasks()?,
+            templates: store.load_templates()?,
+            timer: store.load_timer()?,
+            archived: Vec::new(),
+        })
+    }
+
+    /// Appends tasks to the archive and records them for undo.
+    pub fn append_to_archive(&mut self, tasks: Vec<Task>) -> Result<()> {
+        self.store.append_to_archive(tasks.clone())?;
+        self.archived.extend(tasks);
+        Ok(())
+    }
+
+    /// Records everything that changed since the transaction began as one operation.
+    ///
+    /// Nothing is recorded if nothing changed.
+    pub fn commit(self) -> Result<()> {
+        let mut changes = Vec::new();
+        if !self.archived.is_empty() {
+            changes.push(Change::Archive { tasks: self.archived });
+        }
+
+        changes.extend(diff_tasks(&self.tasks, &self.store.load_tasks()?));
+        changes.extend(diff_templates(&self.templates, &self.store.load_templates()?));
+        let timer = self.store.load_timer()?;
+        if timer != self.timer {
+            changes.push(Change::Timer { before: self.timer, after: timer });
+        }
+
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let timestamp = self.store.clock().timestamp();
+        self.store.append_history(&history::entries(&changes, &self.description, &timestamp))?;
+        let mut journal = self.store.load_journal()?;
+        journal.record(Operation { description: self.description, timestamp, changes });
+        self.store.save_journal(&journal)
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = TaskStore;
+
+    fn deref(&self) -> &TaskStore {
+        self.store
+    }
+}
+
+/// Returns the changes that turn the tasks `before` into `after`, matching tasks by ID.
+pub fn diff_tasks(before: &[Task], after: &[Task]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for b in before {
+        let a = after.iter().find(|t| t.id == b.id);
+        if a != Some(b) {
+            changes.push(Change::Task { before: Some(b.clone()), after: a.cloned() });
+        }
+    }
+    for a in after.iter().filter(|t| !before.iter().any(|b| b.id == t.id)) {
+        changes.push(Change::Task { before: None, after: Some(a.clone()) });
+    }
+    changes
+}
+
+/// Returns the changes that turn the templates `before` into `after`, matching templates by name.
+pub fn diff_templates(before: &[Template], after: &[Template]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for b in before {
+        let a = after.iter().find(|t| t.name == b.name);
+        if a != Some(b) {
+            changes.push(Change::Template { before: Some(b.clone()), after: a.cloned() });
+        }
+    }
+    for a in after.iter().filter(|t| !before.iter().any(|b| b.name == t.name)) {
+        changes.push(Change::Template { before: None, after: Some(a.clone()) });
+    }
+    changes
+}
+
+/// Replaces the task `from` with `to`, keeping tasks ordered by ID when inserting.
+fn replace_task(tasks: &mut Vec<Task>, from: &Option<Task>, to: &Option<Task>) {
+    if let Some(id) = from.as_ref().or(to.as_ref()).map(|t| t.id) {
+        tasks.retain(|t| t.id != id);
+    }
+    if let Some(task) = to {
+        let pos = tasks.iter().position(|t| t.id > task.id).unwrap_or(tasks.len());
+        tasks.insert(pos, task.clone());
+    }
+}
+
+/// Replaces the template `from` with `to`.
+fn replace_template(templates: &mut Vec<Template>, from: &Option<Template>, to: &Option<Template>) {
+    if let Some(name) = from.as_ref().or(to.as_ref()).map(|t| t.name.clone()) {
+        templates.retain(|t| t.name != name);
+    }
+    if let Some(template) = to {
+        templates.push(template.clone());
+    }
+}
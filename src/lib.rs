@@ -1,5 +1,13 @@
+pub mod error;
+pub mod clock;
+pub mod config;
 pub mod models;
 pub mod storage;
 pub mod urgency;
+pub mod deps;
+pub mod journal;
+pub mod history;
+pub mod timer;
+pub mod editor;
 pub mod commands;
 pub mod tui;
@@ -10,6 +10,8 @@
 //!     *   **TUI**: Interactive dashboard to manage tasks visually.
 //! *   **Templates**: Create reusable task templates for common workflows.
 //! *   **Recurrence**: Support for daily, weekly, and monthly recurring tasks.
+//! *   **Tags**: Label tasks (`+review`, `+oncall`) and filter or group by them.
+//! *   **Custom Attributes**: Track story points, customers or anything else as typed fields with their own columns.
 //! *   **Data Persistence**: Tasks are stored in standard XDG data directories (JSON format).
 //! 
 //! ## Installation
@@ -34,20 +36,40 @@
 //! 
 //! **Global**
 //! *   `q`: Quit
+//! *   `Ctrl-z`: Undo the last change
+//! *   `Ctrl-r`: Redo the last undone change
 //! 
 //! **Task View**
 //! *   `a`: Add new task
-//! *   `Space`: Mark selected task as Done
-//! *   `c`: Toggle Show/Hide Completed Tasks
+//! *   `Space`: Mark selected task as Done (press twice if checklist items are still open)
+//! *   `c`: Toggle Show/Hide Done, Cancelled and Waiting Tasks
+//! *   `x`: Cancel selected task
+//! *   `w`: Wait: put the selected task on hold until a date (empty for no date)
 //! *   `d`: Delete selected task
 //! *   `l`: Log hours worked
+//! *   `s`: Start/stop the timer on the selected task
 //! *   `u`: Update remaining estimate
 //! *   `n`: Edit name
 //! *   `p`: Edit project
 //! *   `t`: Edit due date
+//! *   `S`: Edit scheduled date
 //! *   `h`: Edit expected hours
 //! *   `r`: Edit recurrence
+//! *   `m`: Edit template
+//! *   `T`: Edit tags, e.g. `+review +oncall`
+//! *   `P`: Cycle priority (none, low, medium, high)
+//! *   `b`: Edit dependencies, e.g. `3,7`
+//! *   `i`: Add a checklist item to the selected task
+//! *   `A`: Annotate the selected task
+//! *   `E`: Edit the description of the selected task in `$EDITOR`
+//! *   `N`: Toggle the details pane (description and annotations)
+//! *   `Enter`: Expand/collapse a project or tag group, or a task's checklist
+//! *   `Space` on a checklist item: Check/uncheck it (`d` removes it)
+//! *   `/`: Filter by tags, status and custom attributes, e.g. `+review -blocked status:waiting severity:high`
+//! *   `g`: Cycle grouping: none, by project, by tag
 //! *   `v`: Switch to Templates view
+//! *   `H`: Toggle the change history of the selected task
+//! *   `U`: Show how the urgency of the selected task is calculated (`Esc` closes it)
 //! 
 //! **Template View**
 //! *   `a`: Add new template
@@ -68,6 +90,16 @@
 //! 
 //! # From a template
 //! taskust add --template "Bug Report"
+//!
+//! # With tags and a priority
+//! taskust add "Review PR" --due 2025-12-01 --tag review --tag oncall --priority high
+//! taskust edit 3 --tag blocked-on-vendor --untag review
+//!
+//! # Hidden until March 1st, to be started by March 15th
+//! taskust add "Renew certificate" --due 2026-03-31 --wait 2026-03-01 --scheduled 2026-03-15
+//!
+//! # Due at a specific time
+//! taskust add "Submit invoice" --due "2025-12-01 14:00"
 //! ```
 //! 
 //! **Managing Tasks**
@@ -75,23 +107,97 @@
 //! # List tasks (sorted by urgency)
 //! taskust list
 //! 
-//! # List all (including completed)
+//! # List all (including done, cancelled and waiting)
 //! taskust list --all
+//!
+//! # Filter by tags
+//! taskust list --tag review --not-tag blocked-on-vendor
+//!
+//! # Sort by another urgency strategy (see Urgency Calculation)
+//! taskust list --sort-by wsjf
+//!
+//! # Preview the list, urgencies and time left as of another day; only list, show,
+//! # explain, deps, history, item list and template list take it, since it changes nothing
+//! taskust list --as-of 2026-03-09
+//! taskust explain 3 --as-of 2026-03-09
 //! 
 //! # Complete a task
 //! taskust complete <ID>
+//!
+//! # <ID> is the short ID from `list`, or the start of the task's UUID (see `show`),
+//! # which never changes and is never given to another task
+//! taskust show 3
+//! taskust complete 3f2a9c1e
+//!
+//! # Tasks are pending, active (after `start`), waiting, done or cancelled
+//! taskust cancel <ID>
+//! taskust wait <ID> --until 2026-01-15
+//! taskust list --status waiting
+//! taskust edit <ID> --status pending
 //! 
-//! # Log hours
-//! taskust log <ID> --hours 1.5
+//! # Log hours (today by default, or on a given day with a note)
+//! taskust log <ID> 1.5
+//! taskust log <ID> 2 --date 2025-11-28 --note "Code review"
+//!
+//! # Or track time live; the elapsed time is logged on stop
+//! taskust start <ID>
+//! taskust stop
+//!
+//! # Task 5 can't start until 3 and 7 are done; it shows as Blocked until then
+//! taskust edit 5 --depends-on 3,7
+//! taskust deps 5
+//!
+//! # Checklists; progress shows next to the name, e.g. "Release [1/2]"
+//! taskust add "Release" --due 2025-12-01 --item "Tag release" --item "Publish crate"
+//! taskust item check <ID> 1
+//! taskust item list <ID>
+//!
+//! # Notes: a description (opened in $VISUAL or $EDITOR) plus timestamped annotations
+//! taskust edit <ID> --description-editor
+//! taskust annotate <ID> "Waiting on review"
+//! taskust show <ID>
 //! ```
 //! 
 //! **Templates**
 //! ```bash
 //! # Add a template
 //! taskust template add "Bug Report" --project Dev --hours 1.0
+//!
+//! # Tasks from this template start with a checklist of these steps
+//! taskust template add "Release" --step "Bump version" --step "Tag release" --step "Publish crate"
 //! 
 //! # List templates
 //! taskust template list
+//!
+//! # See how a task changed over time (field, old value, new value)
+//! taskust history 3
+//!
+//! # See why a task sorts where it does: each term of its urgency and the total
+//! taskust explain 3
+//!
+//! # Undo a mistake (tasks, templates, archive moves and the timer are reverted together)
+//! taskust undo
+//! taskust redo
+//! ```
+//!
+//! **Custom Attributes**
+//!
+//! Declare extra fields in `config.json` in the data directory. Each has a `type` of `string`, `number`, `date` or `enum` (with its `values`); `label` names its column and `column` shows it in `list` and the TUI:
+//!
+//! ```json
+//! {"udas": [
+//!   {"name": "points", "type": "number", "label": "Pts", "column": true},
+//!   {"name": "severity", "type": "enum", "values": ["low", "high"], "column": true},
+//!   {"name": "customer", "type": "string"}
+//! ]}
+//! ```
+//!
+//! ```bash
+//! taskust add "Fix crash" --due 2025-12-01 --set severity=high --set customer=Acme
+//! taskust edit 3 --set points=5 --set customer=
+//! taskust list --where severity:high
+//! # Tasks created from the template start with its values
+//! taskust template add bug --set severity=high
 //! ```
 //! 
 //! ## Data Storage
@@ -102,24 +208,116 @@
 //! *   Windows: `%APPDATA%\taskust\tasks.json`
 //! 
 //! You can override this by setting the `TASKS_DB` environment variable.
+//!
+//! Every save writes to a temporary file and renames it into place, so a crash never leaves
+//! a truncated database. The previous versions are kept as `tasks.json.1`, `tasks.json.2`, ...
+//! (3 by default, configurable with `TASKS_BACKUPS`; an invalid count is an error) and can be restored with:
+//!
+//! ```bash
+//! taskust restore --backup 1
+//! ```
+//!
+//! Each data file records the format version it was written with (`{"version": 1, "tasks": [...]}`). Files written by older versions of taskust are upgraded automatically when loaded and saved in the new format on the next change; the previous file is kept as a backup. A file written by a newer version is refused rather than risk losing fields this build does not know about.
+//!
+//! ### SQLite Backend
+//!
+//! For large databases and multi-year archives, taskust can store everything in an indexed
+//! SQLite database (`tasks.db`) instead. Build with the `sqlite` feature and convert:
+//!
+//! ```bash
+//! cargo install --path . --features sqlite
+//! taskust migrate --to sqlite
+//! # and back
+//! taskust migrate --to json
+//! ```
+//!
+//! The backend is detected from the files in the data directory, so no further configuration is needed. The converted store is always written as `tasks.db` or `tasks.json`; if `TASKS_DB` names a file, taskust falls back to that file's directory once the file is gone.
+//!
+//! Commands that modify data hold an exclusive lock on `taskust.lock` in the data directory, so the CLI, the TUI and scripts can run at the same time. If the lock cannot be acquired within a few seconds the command fails with "database is locked by pid N".
+//!
+//! Every change is also recorded in `journal.json` in the data directory, which keeps the last 100 operations for `undo` and `redo`, and field-level task changes are appended to `history.jsonl` for `taskust history <id>`. `reset` clears both, along with any running timer (`timer.json`).
+//!
+//! ## Exit Codes
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 2 | I/O error |
+//! | 3 | Data file could not be parsed |
+//! | 4 | Task not found |
+//! | 5 | Template not found |
+//! | 6 | Invalid date |
+//! | 7 | Unknown recurrence pattern |
+//! | 8 | Template already exists |
+//! | 9 | Invalid argument |
+//! | 10 | Backup not found |
+//! | 11 | Database is locked by another process |
+//! | 12 | Database backend error |
+//! | 13 | Data file was written by a newer version of taskust |
+//! | 14 | Task dependencies would form a cycle |
+//! | 15 | No task has the given UUID prefix |
 //! 
 //! ## Urgency Calculation
 //! 
 //! Tasks are scored based on:
-//! 1.  **Due Date**: Closer deadlines = higher urgency. Overdue tasks are critical.
+//! 1.  **Due Date**: Closer deadlines = higher urgency. Overdue tasks are critical. On the last day urgency rises hour by hour towards the deadline.
 //! 2.  **Estimated Effort**: Larger tasks due soon are prioritized over smaller ones.
+//! 3.  **Dependencies**: Blocked tasks have their urgency halved (`blocked_factor`); each open task waiting on a task adds 5 points to it (`blocking`).
+//! 4.  **Priority**: High, medium and low priority add 15, 9.75 and 4.5 points. The weight of a high priority can be changed with `priority` in `config.json` (below); the others scale with it.
+//! 5.  **Scheduled Date**: A pending task whose scheduled date has arrived gets 5 points (`scheduled`), plus 1 point per day it hasn't started (`scheduled_per_day`, up to `scheduled_max_days`, 14).
+//!
+//! Tasks without a due time are due at 23:59, or at the time set in `TASKS_DUE_TIME` (`HH:MM`). Dates and times are in the machine's timezone unless `TASKS_TIMEZONE` names another, e.g. `Europe/Berlin`. Invalid values are reported as errors rather than ignored. Within a day of the deadline the Time Left column counts hours (`3h left`) instead of days.
+//!
+//! `taskust explain <ID>` (or `U` in the TUI) lists the terms that make up a task's score, such as `Overdue`, `Days overdue`, `Effort` and `Priority`, and their total.
+//!
+//! The coefficients can be changed in the `urgency` section of `config.json`. These are the defaults; `age` adds points per day since a task was created (up to `age_max_days`), `tags` and `projects` add points to tasks with a given tag or project, and `high` and `medium` are the scores above which tasks are shown in red and yellow:
+//!
+//! ```json
+//! {"urgency": {
+//!   "strategy": "standard", "mode": "deadline",
+//!   "overdue": 100, "overdue_per_day": 2,
+//!   "due": 10, "effort_hours": 8,
+//!   "hours_per_day": 8, "days_per_week": 5, "at_risk": 20,
+//!   "priority": 15,
+//!   "scheduled": 5, "scheduled_per_day": 1, "scheduled_max_days": 14,
+//!   "blocked_factor": 0.5, "blocking": 5,
+//!   "age": 0, "age_max_days": 365,
+//!   "tags": {}, "projects": {},
+//!   "high": 50, "medium": 20
+//! }}
+//! ```
+//!
+//! **Capacity Mode**: A 20-hour task due in 3 days is in more trouble than one due in 3 weeks. With `"mode": "capacity"` the estimate counts as the hours still to do (estimate minus hours worked), scored by the share of the working hours before the due date they need: `hours_per_day` on each of the first `days_per_week` days of the week, from Monday. A task that needs all of them scores as high as an overdue one.
+//!
+//! In either mode a task that isn't overdue yet but has more work left than working hours is flagged `at risk` in the Time Left column of `list` and the TUI, and `taskust show` prints its hours left to do and available. In capacity mode it also gets `at_risk` points.
+//!
+//! **Strategies**: The model above is the `standard` strategy. `strategy` in `config.json`, or `taskust list --sort-by` for a single listing, picks another:
+//! *   `edf`: Earliest deadline first; the score is minus the days left.
+//! *   `wsjf`: Weighted shortest job first; the cost of delay (1 plus the priority's points) divided by the hours left to do.
+//! *   `priority`: Priority alone.
+//!
+//! `explain`, the TUI and the urgency colours follow the configured strategy. Programs using taskust as a library can add their own by implementing `UrgencyStrategy` and calling `urgency::register_strategy`.
 
 
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::{generate, Shell};
 use std::io;
+use std::process;
 use taskust::commands::*;
+use taskust::config::parse_assignments;
+use taskust::editor::edit_text;
+use taskust::error::{Result, TaskustError};
+use taskust::models::Template;
+use taskust::storage::TaskStore;
 use taskust::tui::run_tui;
 
 #[derive(Parser)]
 #[command(name = "taskust")]
 #[command(about = "Simple terminal task manager", long_about = None)]
 struct Cli {
+    /// Show read-only commands as if today were this date, e.g. to preview the list next Monday
+    #[arg(long, global = true, value_name = "YYYY-MM-DD")]
+    as_of: Option<String>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -136,33 +334,83 @@ enum Commands {
         /// Expected duration in hours (float), e.g. 1.5
         #[arg(short = 'H', long)]
         hours: Option<f64>,
-        /// Due date in YYYY-MM-DD
+        /// Due date in YYYY-MM-DD, optionally with a time: "YYYY-MM-DD HH:MM"
         #[arg(short, long)]
         due: String,
+        /// Day work should start (YYYY-MM-DD); the task gains urgency once it arrives
+        #[arg(long)]
+        scheduled: Option<String>,
+        /// Hide the task until this day (YYYY-MM-DD)
+        #[arg(long)]
+        wait: Option<String>,
         /// Use a template
         #[arg(short, long)]
         template: Option<String>,
         /// Recurrence (daily, weekly, monthly)
         #[arg(short, long)]
         recur: Option<String>,
+        /// Tag to add (repeatable), e.g. --tag review
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Priority (high, medium, low, none)
+        #[arg(short = 'P', long)]
+        priority: Option<String>,
+        /// Checklist item (repeatable), added after the template's steps
+        #[arg(long = "item")]
+        items: Vec<String>,
+        /// Longer notes about the task, e.g. a link to its ticket
+        #[arg(long)]
+        description: Option<String>,
+        /// Set a custom attribute declared in config.json (repeatable), e.g. --set points=3
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        set: Vec<String>,
     },
     /// List tasks sorted by urgency
     List {
-        /// Show completed tasks
+        /// Show done and cancelled tasks
         #[arg(short, long)]
         all: bool,
+        /// Only show tasks with this status (repeatable): pending, active, waiting, done, cancelled
+        #[arg(short, long = "status")]
+        statuses: Vec<String>,
+        /// Only show tasks with this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Hide tasks with this tag (repeatable)
+        #[arg(long = "not-tag")]
+        not_tags: Vec<String>,
+        /// Only show tasks with this custom attribute value (repeatable), e.g. --where severity:high
+        #[arg(long = "where", value_name = "NAME:VALUE")]
+        wheres: Vec<String>,
+        /// Urgency strategy to sort by: standard, edf, wsjf or priority (default from config.json)
+        #[arg(long, value_name = "STRATEGY")]
+        sort_by: Option<String>,
     },
     /// Mark a task as complete
     Complete {
-        id: u64,
+        id: String,
+        /// Don't ask for confirmation if checklist items are still open
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Cancel a task that won't be done
+    Cancel {
+        id: String,
+    },
+    /// Put a task on hold
+    Wait {
+        id: String,
+        /// Day the task becomes pending again (YYYY-MM-DD)
+        #[arg(short, long)]
+        until: Option<String>,
     },
     /// Remove a task
     Remove {
-        id: u64,
+        id: String,
     },
     /// Edit a task
     Edit {
-        id: u64,
+        id: String,
         /// New task name
         #[arg(short, long)]
         name: Option<String>,
@@ -172,28 +420,94 @@ enum Commands {
         /// New expected duration
         #[arg(short = 'H', long)]
         hours: Option<f64>,
-        /// New due date
+        /// New due date, optionally with a time ("YYYY-MM-DD HH:MM")
         #[arg(short, long)]
         due: Option<String>,
+        /// New scheduled date ("" clears)
+        #[arg(long)]
+        scheduled: Option<String>,
         /// New recurrence
         #[arg(short, long)]
         recur: Option<String>,
         /// New template
         #[arg(short, long)]
         template: Option<String>,
+        /// Tag to add (repeatable)
+        #[arg(long = "tag")]
+        add_tags: Vec<String>,
+        /// Tag to remove (repeatable)
+        #[arg(long = "untag")]
+        remove_tags: Vec<String>,
+        /// New priority (high, medium, low, none)
+        #[arg(short = 'P', long)]
+        priority: Option<String>,
+        /// Comma-separated IDs of tasks that must be done first, e.g. 3,7 ("" clears)
+        #[arg(long)]
+        depends_on: Option<String>,
+        /// New description ("" clears)
+        #[arg(long)]
+        description: Option<String>,
+        /// Edit the description in $VISUAL or $EDITOR
+        #[arg(long, conflicts_with = "description")]
+        description_editor: bool,
+        /// New status (pending, active, waiting); reopens a done or cancelled task
+        #[arg(short, long)]
+        status: Option<String>,
+        /// Set a custom attribute (repeatable); an empty value removes it, e.g. --set points=
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        set: Vec<String>,
     },
     /// Log hours worked on a task
     Log {
-        id: u64,
+        id: String,
         /// Hours to add
         hours: f64,
+        /// Day the work was done (YYYY-MM-DD, default today)
+        #[arg(short, long)]
+        date: Option<String>,
+        /// What was worked on
+        #[arg(short, long)]
+        note: Option<String>,
     },
     /// Re-estimate remaining hours for a task
     Estimate {
-        id: u64,
+        id: String,
         /// Remaining hours needed
         remaining: f64,
     },
+    /// Start a timer on a task (stops any running timer)
+    Start {
+        id: String,
+    },
+    /// Stop the running timer and log the elapsed time
+    Stop,
+    /// Add a timestamped note to a task
+    Annotate {
+        id: String,
+        /// Note text
+        text: String,
+    },
+    /// Show a task's details, description and annotations
+    Show {
+        id: String,
+    },
+    /// Show how a task's urgency is calculated
+    Explain {
+        id: String,
+    },
+    /// Show every recorded change to a task
+    History {
+        id: String,
+    },
+    /// Show what a task depends on and what it blocks
+    Deps {
+        id: String,
+    },
+    /// Manage a task's checklist
+    Item {
+        #[command(subcommand)]
+        command: ItemCommands,
+    },
     /// Manage templates
     Template {
         #[command(subcommand)]
@@ -205,9 +519,25 @@ enum Commands {
         #[arg(short, long)]
         force: bool,
     },
-    /// Archive completed tasks
+    /// Undo the last change
+    Undo,
+    /// Redo the last undone change
+    Redo,
+    /// Restore the tasks database from a rotated backup
+    Restore {
+        /// Backup number (1 is the most recent)
+        #[arg(short, long)]
+        backup: usize,
+    },
+    /// Convert the database to another storage backend
+    Migrate {
+        /// Target backend (json, sqlite)
+        #[arg(long)]
+        to: String,
+    },
+    /// Archive done and cancelled tasks
     Archive {
-        /// Archive tasks completed more than X days ago
+        /// Archive tasks closed more than X days ago
         #[arg(short, long)]
         days: Option<i64>,
     },
@@ -232,6 +562,18 @@ enum TemplateCommands {
         /// Default duration
         #[arg(short = 'H', long, default_value_t = 1.0)]
         hours: f64,
+        /// Tag added to tasks created from the template (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Default priority (high, medium, low, none)
+        #[arg(short = 'P', long, default_value = "none")]
+        priority: String,
+        /// Checklist step for tasks created from the template (repeatable)
+        #[arg(long = "step")]
+        steps: Vec<String>,
+        /// Custom attribute value for tasks created from the template (repeatable)
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        set: Vec<String>,
     },
     /// List templates
     List,
@@ -250,46 +592,171 @@ enum TemplateCommands {
         /// New default duration
         #[arg(short = 'H', long)]
         hours: Option<f64>,
+        /// New default priority (high, medium, low, none)
+        #[arg(short = 'P', long)]
+        priority: Option<String>,
+        /// Set a default custom attribute value (repeatable); an empty value removes it
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        set: Vec<String>,
+    }
+}
+
+#[derive(Subcommand)]
+enum ItemCommands {
+    /// Add a checklist item to a task
+    Add {
+        id: String,
+        /// Item text
+        text: String,
+    },
+    /// Mark an item as done
+    Check {
+        id: String,
+        /// Item number, as shown by `item list`
+        n: usize,
+    },
+    /// Mark an item as not done
+    Uncheck {
+        id: String,
+        /// Item number, as shown by `item list`
+        n: usize,
+    },
+    /// Remove an item
+    Remove {
+        id: String,
+        /// Item number, as shown by `item list`
+        n: usize,
+    },
+    /// Show a task's checklist
+    List {
+        id: String,
+    },
+}
+
+impl Commands {
+    /// Returns whether the command only reads the store.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Commands::List { .. }
+                | Commands::Show { .. }
+                | Commands::Explain { .. }
+                | Commands::Deps { .. }
+                | Commands::History { .. }
+                | Commands::Item { command: ItemCommands::List { .. } }
+                | Commands::Template { command: TemplateCommands::List }
+        )
     }
 }
 
 fn main() {
     let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // Completions don't need the store, so a broken environment can't stop them
+    if let Some(Commands::Completions { shell }) = &cli.command {
+        return print_completions(shell);
+    }
+    let store = TaskStore::open_default()?;
+    // --as-of previews; writes made with it would carry made-up timestamps
+    let store = match &cli.as_of {
+        Some(date) if cli.command.as_ref().is_some_and(Commands::is_read_only) => {
+            let clock = store.clock().as_of(parse_date(date)?);
+            store.with_clock(clock)
+        }
+        Some(_) => {
+            return Err(TaskustError::InvalidArgument(
+                "--as-of only works with list, show, explain, deps, history, item list and template list.".to_string(),
+            ))
+        }
+        None => store,
+    };
+    // Tasks can be given by short ID or by a prefix of their UUID
+    let resolve = |id: &str| resolve_id(&store, id);
     match cli.command {
-        Some(Commands::Add { name, project, hours, due, template, recur }) => cmd_add(name, project, hours, due, template, recur, false),
-        Some(Commands::List { all }) => cmd_list(all),
-        Some(Commands::Complete { id }) => cmd_complete(id, false),
-        Some(Commands::Remove { id }) => cmd_remove(id, false),
-        Some(Commands::Edit { id, name, project, hours, due, recur, template }) => cmd_edit(id, name, project, template, hours, None, due, recur, false),
-        Some(Commands::Log { id, hours }) => cmd_log(id, hours, false),
-        Some(Commands::Estimate { id, remaining }) => cmd_estimate(id, remaining, false),
-        Some(Commands::Template { command }) => match command {
-            TemplateCommands::Add { name, project, hours } => cmd_template_add(name, project, hours, false),
-            TemplateCommands::List => cmd_template_list(),
-            TemplateCommands::Remove { name } => cmd_template_remove(name, false),
-            TemplateCommands::Edit { name, project, hours } => cmd_template_edit(name, project, hours, false),
-        },
-        Some(Commands::Reset { force }) => cmd_reset(force),
-        Some(Commands::Archive { days }) => cmd_archive(days, false),
-        Some(Commands::Completions { shell }) => {
-            let shell_enum = match shell.as_str() {
-                "bash" => Shell::Bash,
-                "zsh" => Shell::Zsh,
-                "fish" => Shell::Fish,
-                "powershell" => Shell::PowerShell,
-                "elvish" => Shell::Elvish,
-                _ => {
-                    eprintln!("Unsupported shell: {}", shell);
-                    return;
-                }
-            };
-            let mut cmd = Cli::command();
-            generate(shell_enum, &mut cmd, "taskust", &mut io::stdout());
+        Some(Commands::Add { name, project, hours, due, scheduled, wait, template, recur, tags, priority, items, description, set }) => {
+            let priority = priority.map(|p| p.parse()).transpose()?;
+            let udas = parse_assignments(&set)?;
+            cmd_add(&store, NewTask { name, project, hours, due, scheduled, wait, template, recur, tags, priority, checklist: items, description, udas }, false)
+        }
+        Some(Commands::List { all, statuses, tags, not_tags, wheres, sort_by }) => {
+            let statuses = statuses.iter().map(|s| s.parse()).collect::<Result<_>>()?;
+            let udas = wheres.iter().map(|w| {
+                w.split_once(':').map(|(n, v)| (n.to_string(), v.to_string())).ok_or_else(|| {
+                    TaskustError::InvalidArgument(format!("Invalid condition '{}'. Use name:value.", w))
+                })
+            }).collect::<Result<_>>()?;
+            cmd_list(&store, all, &TaskFilter { statuses, udas, ..TaskFilter::new(tags, not_tags)? }, sort_by.as_deref())
         }
-        Some(Commands::Ui) | None => {
-            if let Err(e) = run_tui() {
-                eprintln!("Error running TUI: {}", e);
+        Some(Commands::Complete { id, force }) => cmd_complete(&store, resolve(&id)?, force, false),
+        Some(Commands::Cancel { id }) => cmd_cancel(&store, resolve(&id)?, false),
+        Some(Commands::Wait { id, until }) => cmd_wait(&store, resolve(&id)?, until, false),
+        Some(Commands::Remove { id }) => cmd_remove(&store, resolve(&id)?, false),
+        Some(Commands::Edit { id, name, project, hours, due, scheduled, recur, template, add_tags, remove_tags, priority, depends_on, mut description, description_editor, status, set }) => {
+            let priority = priority.map(|p| p.parse()).transpose()?;
+            let status = status.map(|s| s.parse()).transpose()?;
+            let depends_on = depends_on.as_deref().map(parse_ids).transpose()?;
+            let id = resolve(&id)?;
+            if description_editor {
+                let current = store.load_task(id)?.description.unwrap_or_default();
+                description = Some(edit_text(&current)?);
             }
+            cmd_edit(&store, id, TaskEdit { name, project, template, hours, due, scheduled, recur, add_tags, remove_tags, priority, depends_on, description, status, udas: parse_assignments(&set)? }, false)
         }
+        Some(Commands::Log { id, hours, date, note }) => cmd_log(&store, resolve(&id)?, hours, date, note, false),
+        Some(Commands::Estimate { id, remaining }) => cmd_estimate(&store, resolve(&id)?, remaining, false),
+        Some(Commands::Start { id }) => cmd_start(&store, resolve(&id)?, false),
+        Some(Commands::Stop) => cmd_stop(&store, false),
+        Some(Commands::Annotate { id, text }) => cmd_annotate(&store, resolve(&id)?, text, false),
+        Some(Commands::Show { id }) => cmd_show(&store, resolve(&id)?),
+        Some(Commands::Explain { id }) => cmd_explain(&store, resolve(&id)?),
+        Some(Commands::History { id }) => cmd_history(&store, &id),
+        Some(Commands::Deps { id }) => cmd_deps(&store, resolve(&id)?),
+        Some(Commands::Item { command }) => match command {
+            ItemCommands::Add { id, text } => cmd_item_add(&store, resolve(&id)?, text, false),
+            ItemCommands::Check { id, n } => cmd_item_check(&store, resolve(&id)?, n, true, false),
+            ItemCommands::Uncheck { id, n } => cmd_item_check(&store, resolve(&id)?, n, false, false),
+            ItemCommands::Remove { id, n } => cmd_item_remove(&store, resolve(&id)?, n, false),
+            ItemCommands::List { id } => cmd_item_list(&store, resolve(&id)?),
+        },
+        Some(Commands::Template { command }) => match command {
+            TemplateCommands::Add { name, project, hours, tags, priority, steps, set } => {
+                let udas = parse_assignments(&set)?.into_iter().collect();
+                let template = Template { name, project, default_hours: hours, tags, priority: priority.parse()?, checklist: steps, udas };
+                cmd_template_add(&store, template, false)
+            }
+            TemplateCommands::List => cmd_template_list(&store),
+            TemplateCommands::Remove { name } => cmd_template_remove(&store, name, false),
+            TemplateCommands::Edit { name, project, hours, priority, set } => {
+                cmd_template_edit(&store, name, project, hours, priority.map(|p| p.parse()).transpose()?, parse_assignments(&set)?, false)
+            }
+        },
+        Some(Commands::Reset { force }) => cmd_reset(&store, force),
+        Some(Commands::Undo) => cmd_undo(&store, false),
+        Some(Commands::Redo) => cmd_redo(&store, false),
+        Some(Commands::Restore { backup }) => cmd_restore(&store, backup, false),
+        Some(Commands::Migrate { to }) => cmd_migrate(&store, to.parse()?, false),
+        Some(Commands::Archive { days }) => cmd_archive(&store, days, false),
+        Some(Commands::Completions { .. }) => unreachable!("completions are printed before the store is opened"),
+        Some(Commands::Ui) | None => run_tui(store),
     }
 }
+
+fn print_completions(shell: &str) -> Result<()> {
+    let shell_enum = match shell {
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        "fish" => Shell::Fish,
+        "powershell" => Shell::PowerShell,
+        "elvish" => Shell::Elvish,
+        _ => return Err(TaskustError::InvalidArgument(format!("Unsupported shell: {}", shell))),
+    };
+    let mut cmd = Cli::command();
+    generate(shell_enum, &mut cmd, "taskust", &mut io::stdout());
+    Ok(())
+}
@@ -1,11 +1,19 @@
-use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::TaskustError;
 
 /// Represents a single task in the task manager.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Task {
     /// Unique identifier for the task.
     pub id: u64,
+    /// Identifier that never changes. Unlike `id`, which is short enough to type, it is
+    /// not given to another task once this one is archived or removed.
+    pub uuid: Uuid,
     /// The name or description of the task.
     pub name: String,
     /// Optional project or category the task belongs to.
@@ -14,27 +22,139 @@ pub struct Task {
     pub expected_hours: f64,
     /// The due date of the task.
     pub due_date: NaiveDate,
+    /// The time of day the task is due, if it has one. Tasks without one are due at the
+    /// configured default time (see [`Clock::from_env`](crate::clock::Clock::from_env)).
+    #[serde(default)]
+    pub due_time: Option<NaiveTime>,
+    /// The day work on the task should start, if planned.
+    #[serde(default)]
+    pub scheduled: Option<NaiveDate>,
     /// Timestamp when the task was created (ISO 8601).
     pub created_at: String,
-    /// Whether the task has been completed.
+    /// Where the task is in its lifecycle.
     #[serde(default)]
-    pub completed: bool,
-    /// Total hours actually worked on the task.
+    pub status: Status,
+    /// For a waiting task, the day it becomes pending again, if known. Until then the
+    /// task is hidden from default listings.
     #[serde(default)]
-    pub hours_worked: f64,
+    pub wait_until: Option<NaiveDate>,
+    /// Time logged on the task, in the order it was logged.
+    #[serde(default)]
+    pub work_log: Vec<WorkEntry>,
     /// Name of the template used to create this task, if any.
     #[serde(default)]
     pub template: Option<String>,
     /// Recurrence pattern (e.g., "daily", "weekly", "monthly").
     #[serde(default)]
     pub recurrence: Option<String>,
-    /// Timestamp when the task was completed (ISO 8601).
+    /// Timestamp when the task was completed or cancelled (ISO 8601).
     #[serde(default)]
     pub completed_at: Option<String>,
+    /// Free-form labels such as `review` or `oncall`, without the leading `+`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How important the task is, independent of its due date.
+    #[serde(default)]
+    pub priority: Priority,
+    /// UUIDs of tasks that must be completed before this one can start. Dependencies
+    /// keep pointing at the same task when it is archived and its short ID reused.
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+    /// Ordered steps to tick off while working on the task.
+    #[serde(default)]
+    pub checklist: Vec<ChecklistItem>,
+    /// Longer, possibly multi-line notes about the task, such as a link to its ticket.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Timestamped notes added over the life of the task, oldest first.
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Values of user-defined attributes by name, see [`Config`](crate::config::Config).
+    #[serde(default)]
+    pub udas: BTreeMap<String, String>,
+}
+
+impl Task {
+    /// Total hours actually worked on the task, summed over its work log.
+    pub fn hours_worked(&self) -> f64 {
+        // fold from 0.0: an empty `sum` of floats is -0.0, which displays as "-0.0"
+        self.work_log.iter().fold(0.0, |total, e| total + e.hours)
+    }
+
+    /// Hours of work still left: the estimate minus the hours worked, never negative.
+    pub fn remaining_hours(&self) -> f64 {
+        (self.expected_hours - self.hours_worked()).max(0.0)
+    }
+
+    /// Returns whether the task is done or cancelled.
+    pub fn is_closed(&self) -> bool {
+        self.status.is_closed()
+    }
+
+    /// Returns the task's status on `today`: a waiting task whose wait date has passed
+    /// is pending again.
+    pub fn status_on(&self, today: NaiveDate) -> Status {
+        match (self.status, self.wait_until) {
+            (Status::Waiting, Some(until)) if until <= today => Status::Pending,
+            (status, _) => status,
+        }
+    }
+
+    /// Returns whether default listings hide the task on `today` because it is waiting
+    /// until a later date.
+    pub fn is_hidden_on(&self, today: NaiveDate) -> bool {
+        self.status_on(today) == Status::Waiting && self.wait_until.is_some()
+    }
+
+    /// Returns whether the task has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns the number of checklist items not yet done.
+    pub fn open_items(&self) -> usize {
+        self.checklist.iter().filter(|i| !i.done).count()
+    }
+
+    /// Returns checklist progress such as `3/8`, or `None` if the task has no checklist.
+    pub fn progress(&self) -> Option<String> {
+        if self.checklist.is_empty() {
+            return None;
+        }
+        Some(format!("{}/{}", self.checklist.len() - self.open_items(), self.checklist.len()))
+    }
+}
+
+/// A step in a task's checklist.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChecklistItem {
+    pub text: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// A timestamped note on a task.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// When the note was added (ISO 8601).
+    pub timestamp: String,
+    pub text: String,
+}
+
+/// A block of time worked on a task.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkEntry {
+    /// The day the work was done.
+    pub date: NaiveDate,
+    /// Hours worked.
+    pub hours: f64,
+    /// Optional note describing the work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
 /// Represents a reusable task template.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Template {
     /// The unique name of the template.
     pub name: String,
@@ -42,4 +162,130 @@ pub struct Template {
     pub project: Option<String>,
     /// Default estimated duration for tasks created from this template.
     pub default_hours: f64,
+    /// Tags added to tasks created from this template.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Default priority for tasks created from this template.
+    #[serde(default)]
+    pub priority: Priority,
+    /// Checklist steps every task created from this template starts with.
+    #[serde(default)]
+    pub checklist: Vec<String>,
+    /// Values of user-defined attributes tasks created from this template start with.
+    #[serde(default)]
+    pub udas: BTreeMap<String, String>,
+}
+
+/// The importance of a task, which raises its urgency.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    /// Returns the share of the priority weight this priority adds to urgency, from 0 to 1.
+    pub fn coefficient(self) -> f64 {
+        match self {
+            Priority::None => 0.0,
+            Priority::Low => 0.3,
+            Priority::Medium => 0.65,
+            Priority::High => 1.0,
+        }
+    }
+
+    /// Returns the next priority in the order None, Low, Medium, High, None.
+    pub fn cycle(self) -> Priority {
+        match self {
+            Priority::None => Priority::Low,
+            Priority::Low => Priority::Medium,
+            Priority::Medium => Priority::High,
+            Priority::High => Priority::None,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::None => write!(f, "None"),
+            Priority::Low => write!(f, "Low"),
+            Priority::Medium => write!(f, "Medium"),
+            Priority::High => write!(f, "High"),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = TaskustError;
+
+    fn from_str(s: &str) -> Result<Priority, TaskustError> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" | "n" => Ok(Priority::None),
+            "low" | "l" => Ok(Priority::Low),
+            "medium" | "m" => Ok(Priority::Medium),
+            "high" | "h" => Ok(Priority::High),
+            _ => Err(TaskustError::InvalidArgument(format!(
+                "Unknown priority '{}'. Supported: high, medium, low, none.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Where a task is in its lifecycle.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Not started yet.
+    #[default]
+    Pending,
+    /// Being worked on.
+    Active,
+    /// On hold, e.g. for someone else, optionally until a date.
+    Waiting,
+    Done,
+    /// Dropped without being done.
+    Cancelled,
+}
+
+impl Status {
+    /// Returns whether no more work is expected: the task is done or cancelled.
+    pub fn is_closed(self) -> bool {
+        matches!(self, Status::Done | Status::Cancelled)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pending => write!(f, "Pending"),
+            Status::Active => write!(f, "Active"),
+            Status::Waiting => write!(f, "Waiting"),
+            Status::Done => write!(f, "Done"),
+            Status::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = TaskustError;
+
+    fn from_str(s: &str) -> Result<Status, TaskustError> {
+        match s.trim().to_lowercase().as_str() {
+            "pending" => Ok(Status::Pending),
+            "active" => Ok(Status::Active),
+            "waiting" => Ok(Status::Waiting),
+            "done" | "completed" => Ok(Status::Done),
+            "cancelled" | "canceled" => Ok(Status::Cancelled),
+            _ => Err(TaskustError::InvalidArgument(format!(
+                "Unknown status '{}'. Supported: pending, active, waiting, done, cancelled.",
+                s
+            ))),
+        }
+    }
 }
@@ -0,0 +1,241 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::error::{Result, TaskustError};
+use crate::models::{Task, Template};
+use super::schema::{self, Collection};
+use super::{resolve_location, Backend, Storage};
+
+/// Number of rotated backups kept for each data file unless configured otherwise.
+pub const DEFAULT_BACKUPS: usize = 3;
+
+/// Stores tasks, templates and the archive as pretty-printed JSON files.
+///
+/// Each file holds a versioned envelope (see [`super::schema`]). Every save replaces the
+/// file atomically and keeps rotated backups next to it.
+#[derive(Debug, Clone)]
+pub struct JsonStorage {
+    tasks_path: PathBuf,
+    templates_path: PathBuf,
+    archive_path: PathBuf,
+    backups: usize,
+}
+
+impl JsonStorage {
+    /// Opens JSON storage at the given path.
+    ///
+    /// `path` is either a data directory or the tasks file itself; see [`super::TaskStore::open`].
+    pub fn open(path: impl AsRef<Path>) -> JsonStorage {
+        let (dir, file) = resolve_location(path.as_ref());
+        JsonStorage {
+            tasks_path: file.unwrap_or_else(|| dir.join("tasks.json")),
+            templates_path: dir.join("templates.json"),
+            archive_path: dir.join("archive.json"),
+            backups: DEFAULT_BACKUPS,
+        }
+    }
+
+    /// Sets how many rotated backups (`tasks.json.1`, `tasks.json.2`, ...) are kept
+    /// next to each data file. `0` disables backups.
+    pub fn with_backups(mut self, backups: usize) -> JsonStorage {
+        self.backups = backups;
+        self
+    }
+
+    /// Returns the path to the tasks database file (`tasks.json`).
+    pub fn tasks_path(&self) -> &Path {
+        &self.tasks_path
+    }
+
+    /// Returns the path to the templates database file (`templates.json`).
+    pub fn templates_path(&self) -> &Path {
+        &self.templates_path
+    }
+
+    /// Returns the path to the archive database file (`archive.json`).
+    pub fn archive_path(&self) -> &Path {
+        &self.archive_path
+    }
+
+    /// Returns the number of rotated backups kept for each data file.
+    pub fn backups(&self) -> usize {
+        self.backups
+    }
+
+    /// Returns the path of the `n`-th backup of the tasks file (`tasks.json.n`).
+    pub fn backup_path(&self, n: usize) -> PathBuf {
+        numbered_path(&self.tasks_path, n)
+    }
+}
+
+impl Storage for JsonStorage {
+    fn backend(&self) -> Backend {
+        Backend::Json
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>> {
+        read_json(&self.tasks_path, Collection::Tasks)
+    }
+
+    fn save_tasks(&self, tasks: &[Task]) -> Result<()> {
+        write_json(&self.tasks_path, Collection::Tasks, tasks, self.backups)
+    }
+
+    fn load_templates(&self) -> Result<Vec<Template>> {
+        read_json(&self.templates_path, Collection::Templates)
+    }
+
+    fn save_templates(&self, templates: &[Template]) -> Result<()> {
+        write_json(&self.templates_path, Collection::Templates, templates, self.backups)
+    }
+
+    fn load_archived_tasks(&self) -> Result<Vec<Task>> {
+        read_json(&self.archive_path, Collection::Archive)
+    }
+
+    fn append_to_archive(&self, new_tasks: Vec<Task>) -> Result<()> {
+        let mut archive = self.load_archived_tasks()?;
+        archive.extend(new_tasks);
+        write_json(&self.archive_path, Collection::Archive, &archive, self.backups)
+    }
+
+    fn pop_archive(&self, count: usize) -> Result<()> {
+        let mut archive = self.load_archived_tasks()?;
+        archive.truncate(archive.len().saturating_sub(count));
+        write_json(&self.archive_path, Collection::Archive, &archive, self.backups)
+    }
+
+    fn delete_database(&self) -> Result<()> {
+        if self.tasks_path.exists() {
+            fs::remove_file(&self.tasks_path)?;
+        }
+        if self.templates_path.exists() {
+            fs::remove_file(&self.templates_path)?;
+        }
+        Ok(())
+    }
+
+    fn destroy(&self) -> Result<()> {
+        self.delete_database()?;
+        if self.archive_path.exists() {
+            fs::remove_file(&self.archive_path)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the tasks file from its `n`-th backup.
+    ///
+    /// The backup is validated before it is written. The current tasks file is rotated
+    /// like any other save, so the restore itself can be rolled back with `--backup 1`.
+    fn restore_backup(&self, n: usize) -> Result<()> {
+        let path = self.backup_path(n);
+        if n == 0 || !path.exists() {
+            return Err(TaskustError::BackupNotFound(path));
+        }
+        let tasks: Vec<Task> = read_json(&path, Collection::Tasks)?;
+        self.save_tasks(&tasks)
+    }
+}
+
+/// Reads a versioned JSON list from `path`, upgrading it to the current format.
+///
+/// Returns an empty vector if the file does not exist. A file that exists but cannot be
+/// parsed is reported as [`TaskustError::Parse`] rather than treated as empty, so a
+/// subsequent save never overwrites data that merely failed to load.
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path, collection: Collection) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut f = OpenOptions::new().read(true).open(path)?;
+    let mut s = String::new();
+    f.read_to_string(&mut s)?;
+    let parse_error = |e: serde_json::Error| TaskustError::Parse {
+        file: path.to_path_buf(),
+        line: e.line(),
+        message: e.to_string(),
+    };
+    let document = serde_json::from_str(&s).map_err(parse_error)?;
+    schema::unwrap(collection, document, path)?
+        .into_iter()
+        .map(|item| serde_json::from_value(item).map_err(parse_error))
+        .collect()
+}
+
+/// Writes `items` to `path` as a pretty-printed versioned envelope, replacing the file atomically.
+fn write_json<T: serde::Serialize>(path: &Path, collection: Collection, items: &[T], backups: usize) -> Result<()> {
+    let items = serde_json::to_value(items).map_err(io::Error::other)?;
+    let s = serde_json::to_string_pretty(&schema::wrap(collection, items)).map_err(io::Error::other)?;
+    write_atomic(path, s.as_bytes(), backups)
+}
+
+/// Replaces the contents of `path` atomically.
+///
+/// The data is written and fsynced to a temporary file next to `path`, the previous
+/// contents are rotated into up to `backups` numbered backups, and the temporary file is
+/// then renamed over `path`. A crash at any point leaves either the old or the new file.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8], backups: usize) -> Result<()> {
+    let tmp_path = suffixed_path(path, "tmp");
+    let mut f = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    f.write_all(contents)?;
+    f.sync_all()?;
+    drop(f);
+
+    if backups > 0 && path.exists() {
+        rotate_backups(path, backups)?;
+    }
+    fs::rename(&tmp_path, path)?;
+    sync_dir(path);
+    Ok(())
+}
+
+/// Shifts `path.1 .. path.{n-1}` up by one and links the current `path` as `path.1`.
+///
+/// The oldest backup falls off the end. Hard links are used so rotating never copies
+/// data; filesystems without hard links fall back to a copy.
+fn rotate_backups(path: &Path, backups: usize) -> Result<()> {
+    for i in (1..backups).rev() {
+        let from = numbered_path(path, i);
+        if from.exists() {
+            fs::rename(&from, numbered_path(path, i + 1))?;
+        }
+    }
+    let first = numbered_path(path, 1);
+    if first.exists() {
+        fs::remove_file(&first)?;
+    }
+    if fs::hard_link(path, &first).is_err() {
+        fs::copy(path, &first)?;
+    }
+    Ok(())
+}
+
+/// Flushes the directory entry of `path` so a completed rename survives a crash.
+///
+/// Only meaningful on Unix; failures are ignored since the data itself is already synced.
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        if let Ok(d) = fs::File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Returns `path` with `.n` appended, e.g. `tasks.json.2`.
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    suffixed_path(path, &n.to_string())
+}
+
+/// Returns `path` with `.suffix` appended to the file name.
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
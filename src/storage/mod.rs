@@ -0,0 +1,531 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::error::{Result, TaskustError};
+use crate::history::{self, HistoryEntry};
+use crate::journal::{Journal, Transaction};
+use crate::timer::Timer;
+use crate::models::{Task, Template};
+use uuid::Uuid;
+
+pub mod json;
+pub mod schema;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use json::{JsonStorage, DEFAULT_BACKUPS};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+/// File name of the SQLite database inside a data directory.
+pub const SQLITE_FILE: &str = "tasks.db";
+
+/// How long [`TaskStore::lock`] waits for another process before giving up.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The storage backends taskust can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Pretty-printed JSON files (`tasks.json`, `templates.json`, `archive.json`).
+    Json,
+    /// A single indexed SQLite database (`tasks.db`). Requires the `sqlite` feature.
+    Sqlite,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Json => write!(f, "json"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = TaskustError;
+
+    fn from_str(s: &str) -> Result<Backend> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Backend::Json),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(TaskustError::InvalidArgument(format!(
+                "Unknown storage backend '{}'. Supported: json, sqlite.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Persistence for tasks, templates and the archive.
+///
+/// [`JsonStorage`] is the default implementation; `SqliteStorage` is available with the
+/// `sqlite` cargo feature. Implementations do no locking of their own; callers go
+/// through [`TaskStore`], which serializes writers.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// Returns which backend this is.
+    fn backend(&self) -> Backend;
+
+    /// Loads all tasks. Returns an empty vector if nothing has been saved yet.
+    fn load_tasks(&self) -> Result<Vec<Task>>;
+
+    /// Replaces all tasks with `tasks`.
+    fn save_tasks(&self, tasks: &[Task]) -> Result<()>;
+
+    /// Saves or updates a single task.
+    ///
+    /// If the task with the same ID exists, it is updated; otherwise, it is added.
+    fn save_task(&self, task: &Task) -> Result<()> {
+        let mut tasks = self.load_tasks()?;
+        if let Some(t) = tasks.iter_mut().find(|t| t.id == task.id) {
+            *t = task.clone();
+        } else {
+            tasks.push(task.clone());
+        }
+        self.save_tasks(&tasks)
+    }
+
+    /// Loads all templates.
+    fn load_templates(&self) -> Result<Vec<Template>>;
+
+    /// Replaces all templates with `templates`.
+    fn save_templates(&self, templates: &[Template]) -> Result<()>;
+
+    /// Loads all archived tasks.
+    fn load_archived_tasks(&self) -> Result<Vec<Task>>;
+
+    /// Appends tasks to the archive.
+    fn append_to_archive(&self, tasks: Vec<Task>) -> Result<()>;
+
+    /// Removes the `count` most recently archived tasks.
+    fn pop_archive(&self, count: usize) -> Result<()>;
+
+    /// Deletes all tasks and templates. The archive is kept.
+    fn delete_database(&self) -> Result<()>;
+
+    /// Deletes everything this backend has stored, including the archive, and removes
+    /// its data files so the backend is no longer detected in the data directory.
+    fn destroy(&self) -> Result<()>;
+
+    /// Restores the tasks from the `n`-th backup, for backends that keep backups.
+    fn restore_backup(&self, n: usize) -> Result<()> {
+        let _ = n;
+        Err(TaskustError::InvalidArgument(format!(
+            "The {} backend does not keep backups.",
+            self.backend()
+        )))
+    }
+}
+
+/// A handle to a task database.
+///
+/// A store owns the data directory and a [`Storage`] backend, so several independent
+/// stores can be open in the same process.
+#[derive(Debug, Clone)]
+pub struct TaskStore {
+    dir: PathBuf,
+    storage: Arc<dyn Storage>,
+    lock_path: PathBuf,
+    journal_path: PathBuf,
+    history_path: PathBuf,
+    timer_path: PathBuf,
+    config_path: PathBuf,
+    lock_timeout: Duration,
+    clock: Clock,
+}
+
+/// An exclusive lock on a store, released when dropped.
+///
+/// Obtained from [`TaskStore::lock`] and held across a load → mutate → save cycle so
+/// concurrent CLI invocations, the TUI and scripts never overwrite each other's changes.
+#[derive(Debug)]
+pub struct StoreLock {
+    file: File,
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        // Clear our pid; the OS releases the lock when the file is closed.
+        let _ = self.file.set_len(0);
+    }
+}
+
+impl TaskStore {
+    /// Opens a store at the given path, detecting the backend.
+    ///
+    /// If `path` is an existing directory, or does not exist and has no `.json`, `.db` or
+    /// `.sqlite` extension, it is treated as a data directory. The SQLite backend is used
+    /// if the directory contains `tasks.db`, otherwise the JSON files `tasks.json`,
+    /// `templates.json` and `archive.json`. If `path` is a file, it is the tasks database
+    /// itself and its extension selects the backend. A file that does not exist yet falls
+    /// back to the directory it would be in when that holds `tasks.db` or `tasks.json`,
+    /// which is where [`crate::commands::cmd_migrate`] leaves the converted store.
+    ///
+    /// The store's clock is read from the environment, see [`Clock::from_env`].
+    pub fn open(path: impl AsRef<Path>) -> Result<TaskStore> {
+        let (path, backend) = locate(path.as_ref());
+        TaskStore::open_backend(path, backend)
+    }
+
+    /// Opens a store at the given path using a specific backend.
+    pub fn open_backend(path: impl AsRef<Path>, backend: Backend) -> Result<TaskStore> {
+        let path = path.as_ref();
+        let (dir, _) = resolve_location(path);
+        let store = match backend {
+            Backend::Json => TaskStore::from_storage(dir, JsonStorage::open(path)),
+            Backend::Sqlite => open_sqlite(path, dir)?,
+        };
+        Ok(store.with_clock(Clock::from_env()?))
+    }
+
+    /// Creates a store around an existing storage implementation.
+    ///
+    /// `dir` is the data directory used for the lock file. The store uses a
+    /// [system clock](Clock::system); see [`TaskStore::with_clock`].
+    pub fn from_storage(dir: impl Into<PathBuf>, storage: impl Storage + 'static) -> TaskStore {
+        let dir = dir.into();
+        TaskStore {
+            lock_path: dir.join("taskust.lock"),
+            journal_path: dir.join("journal.json"),
+            history_path: dir.join("history.jsonl"),
+            timer_path: dir.join("timer.json"),
+            config_path: dir.join("config.json"),
+            dir,
+            storage: Arc::new(storage),
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            clock: Clock::system(),
+        }
+    }
+
+    /// Opens the default store.
+    ///
+    /// The location is determined in the following order:
+    /// 1. `TASKS_DB` environment variable (path to the tasks file or data directory).
+    /// 2. `~/.local/share/taskust/` (on Linux).
+    /// 3. `./` (fallback).
+    ///
+    /// The number of JSON backups can be overridden with the `TASKS_BACKUPS` environment variable
+    /// (an invalid count is an error), and the clock is read from the environment as for
+    /// [`TaskStore::open`].
+    pub fn open_default() -> Result<TaskStore> {
+        let path = std::env::var("TASKS_DB").map(PathBuf::from).unwrap_or_else(|_| {
+            let mut p = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+            p.push("taskust");
+            p
+        });
+        let backups = match std::env::var("TASKS_BACKUPS") {
+            Ok(n) => Some(n.trim().parse().map_err(|_| {
+                TaskustError::InvalidArgument(format!("Invalid TASKS_BACKUPS '{}'", n))
+            })?),
+            Err(_) => None,
+        };
+
+        let (path, backend) = locate(&path);
+        match backend {
+            Backend::Json => {
+                let (dir, _) = resolve_location(&path);
+                let json = JsonStorage::open(&path).with_backups(backups.unwrap_or(DEFAULT_BACKUPS));
+                Ok(TaskStore::from_storage(dir, json).with_clock(Clock::from_env()?))
+            }
+            backend => TaskStore::open_backend(&path, backend),
+        }
+    }
+
+    /// Sets how long [`TaskStore::lock`] waits for a lock held by another process.
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> TaskStore {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Sets the clock commands on this store take the current time from.
+    pub fn with_clock(mut self, clock: Clock) -> TaskStore {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the clock commands on this store take the current time from.
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Returns the data directory of this store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the storage backend in use.
+    pub fn backend(&self) -> Backend {
+        self.storage.backend()
+    }
+
+    /// Returns the underlying storage implementation.
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Returns the path to the lock file (`taskust.lock`).
+    pub fn lock_path(&self) -> &Path {
+        &self.lock_path
+    }
+
+    /// Returns the path to the undo journal (`journal.json`).
+    pub fn journal_path(&self) -> &Path {
+        &self.journal_path
+    }
+
+    /// Returns the path to the per-task change history (`history.jsonl`).
+    pub fn history_path(&self) -> &Path {
+        &self.history_path
+    }
+
+    /// Returns the path to the running timer (`timer.json`).
+    pub fn timer_path(&self) -> &Path {
+        &self.timer_path
+    }
+
+    /// Returns the path to the configuration file (`config.json`).
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Acquires an exclusive advisory lock on the store.
+    ///
+    /// Waits up to the configured timeout for other holders, then fails with
+    /// [`TaskustError::Locked`] naming the process that holds the lock. The lock is not
+    /// reentrant: acquiring it twice from the same process blocks until the timeout.
+    pub fn lock(&self) -> Result<StoreLock> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock_path)?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if start.elapsed() < self.lock_timeout => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let pid = fs::read_to_string(&self.lock_path)
+                        .ok()
+                        .and_then(|s| s.trim().parse().ok());
+                    return Err(TaskustError::Locked { pid });
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(StoreLock { file })
+    }
+
+    /// Locks the store and starts a [`Transaction`] that is recorded in the undo journal
+    /// as `description` when committed.
+    pub fn begin(&self, description: impl Into<String>) -> Result<Transaction<'_>> {
+        Transaction::begin(self, description)
+    }
+
+    /// Loads the undo journal.
+    pub fn load_journal(&self) -> Result<Journal> {
+        Journal::load(&self.journal_path)
+    }
+
+    /// Saves the undo journal.
+    pub fn save_journal(&self, journal: &Journal) -> Result<()> {
+        let s = serde_json::to_string(journal).map_err(std::io::Error::other)?;
+        json::write_atomic(&self.journal_path, s.as_bytes(), 0)
+    }
+
+    /// Deletes the undo journal.
+    pub fn clear_journal(&self) -> Result<()> {
+        if self.journal_path.exists() {
+            fs::remove_file(&self.journal_path)?;
+        }
+        Ok(())
+    }
+
+    /// Appends entries to the per-task change history.
+    pub fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        history::append(&self.history_path, entries)
+    }
+
+    /// Loads the recorded changes to task `id`, oldest first, see [`history::load`].
+    pub fn load_history(&self, id: u64, uuid: Option<Uuid>) -> Result<Vec<HistoryEntry>> {
+        history::load(&self.history_path, id, uuid)
+    }
+
+    /// Deletes the per-task change history.
+    pub fn clear_history(&self) -> Result<()> {
+        if self.history_path.exists() {
+            fs::remove_file(&self.history_path)?;
+        }
+        Ok(())
+    }
+
+    /// Loads the configuration, or the defaults if there is no `config.json`.
+    pub fn load_config(&self) -> Result<Config> {
+        Config::load(&self.config_path)
+    }
+
+    /// Loads the running timer, if any.
+    pub fn load_timer(&self) -> Result<Option<Timer>> {
+        Timer::load(&self.timer_path)
+    }
+
+    /// Saves the running timer.
+    pub fn save_timer(&self, timer: &Timer) -> Result<()> {
+        let s = serde_json::to_string_pretty(timer).map_err(std::io::Error::other)?;
+        json::write_atomic(&self.timer_path, s.as_bytes(), 0)
+    }
+
+    /// Stops tracking the running timer without logging anything.
+    pub fn clear_timer(&self) -> Result<()> {
+        if self.timer_path.exists() {
+            fs::remove_file(&self.timer_path)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a single task by its ID.
+    ///
+    /// Returns [`TaskustError::NotFound`] if the task is not found.
+    pub fn load_task(&self, id: u64) -> Result<Task> {
+        self.load_tasks()?
+            .into_iter()
+            .find(|t| t.id == id)
+            .ok_or(TaskustError::NotFound(id))
+    }
+
+    /// Loads all tasks.
+    ///
+    /// Returns an empty vector if nothing has been saved yet.
+    pub fn load_tasks(&self) -> Result<Vec<Task>> {
+        self.storage.load_tasks()
+    }
+
+    /// Saves or updates a single task.
+    ///
+    /// If the task with the same ID exists, it is updated; otherwise, it is added.
+    pub fn save_task(&self, task: &Task) -> Result<()> {
+        self.storage.save_task(task)
+    }
+
+    /// Saves the given list of tasks, replacing the existing ones.
+    pub fn save_tasks(&self, tasks: &[Task]) -> Result<()> {
+        self.storage.save_tasks(tasks)
+    }
+
+    /// Loads all templates.
+    pub fn load_templates(&self) -> Result<Vec<Template>> {
+        self.storage.load_templates()
+    }
+
+    /// Saves the given list of templates, replacing the existing ones.
+    pub fn save_templates(&self, templates: &[Template]) -> Result<()> {
+        self.storage.save_templates(templates)
+    }
+
+    /// Loads a single template by its name.
+    ///
+    /// Returns `None` if no template has that name.
+    pub fn load_template(&self, name: &str) -> Result<Option<Template>> {
+        Ok(self.load_templates()?.into_iter().find(|t| t.name == name))
+    }
+
+    /// Deletes all tasks and templates.
+    pub fn delete_database(&self) -> Result<()> {
+        self.storage.delete_database()
+    }
+
+    /// Loads all archived tasks.
+    pub fn load_archived_tasks(&self) -> Result<Vec<Task>> {
+        self.storage.load_archived_tasks()
+    }
+
+    /// Appends tasks to the archive.
+    pub fn append_to_archive(&self, new_tasks: Vec<Task>) -> Result<()> {
+        self.storage.append_to_archive(new_tasks)
+    }
+
+    /// Removes the `count` most recently archived tasks.
+    pub fn pop_archive(&self, count: usize) -> Result<()> {
+        self.storage.pop_archive(count)
+    }
+
+    /// Restores the tasks from the `n`-th backup (JSON backend only).
+    pub fn restore_backup(&self, n: usize) -> Result<()> {
+        self.storage.restore_backup(n)
+    }
+}
+
+/// Splits a store path into its data directory and, if `path` names a file, that file.
+///
+/// The directory is created if it does not exist.
+fn resolve_location(path: &Path) -> (PathBuf, Option<PathBuf>) {
+    let is_file = if path.exists() {
+        !path.is_dir()
+    } else {
+        path.extension().is_some_and(|ext| ext == "json" || ext == "db" || ext == "sqlite")
+    };
+
+    let (dir, file) = if is_file {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        (dir, Some(path.to_path_buf()))
+    } else {
+        (path.to_path_buf(), None)
+    };
+    if !dir.as_os_str().is_empty() && !dir.exists() {
+        let _ = fs::create_dir_all(&dir);
+    }
+    (dir, file)
+}
+
+/// Picks the path to open and its backend for a store path, see [`TaskStore::open`].
+fn locate(path: &Path) -> (PathBuf, Backend) {
+    let (dir, file) = resolve_location(path);
+    match file {
+        // A missing file next to an existing store, e.g. after a migration
+        Some(f) if !f.exists() && (dir.join(SQLITE_FILE).exists() || dir.join("tasks.json").exists()) => {
+            let backend = detect_backend(&dir, None);
+            (dir, backend)
+        }
+        file => {
+            let backend = detect_backend(&dir, file.as_deref());
+            (path.to_path_buf(), backend)
+        }
+    }
+}
+
+/// Picks the backend for a resolved location.
+fn detect_backend(dir: &Path, file: Option<&Path>) -> Backend {
+    match file {
+        Some(f) if f.extension().is_some_and(|ext| ext == "db" || ext == "sqlite") => Backend::Sqlite,
+        Some(_) => Backend::Json,
+        None if dir.join(SQLITE_FILE).exists() => Backend::Sqlite,
+        None => Backend::Json,
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &Path, dir: PathBuf) -> Result<TaskStore> {
+    let file = if path == dir { dir.join(SQLITE_FILE) } else { path.to_path_buf() };
+    Ok(TaskStore::from_storage(dir, SqliteStorage::open(file)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(_path: &Path, _dir: PathBuf) -> Result<TaskStore> {
+    Err(TaskustError::InvalidArgument(
+        "This build of taskust has no SQLite support. Rebuild with `--features sqlite`.".to_string(),
+    ))
+}
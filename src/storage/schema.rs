@@ -0,0 +1,248 @@
+use std::path::Path;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+use crate::error::{Result, TaskustError};
+use Migration::{All, Each};
+
+/// Format version written by this build of taskust.
+///
+/// Bump this whenever the stored shape of a task or template changes, and append a
+/// migration for the previous version to [`TASK_MIGRATIONS`] and [`TEMPLATE_MIGRATIONS`].
+pub const CURRENT_VERSION: u32 = 13;
+
+/// Upgrades the stored items of a collection from one version to the next.
+#[derive(Clone, Copy)]
+enum Migration {
+    /// Upgrades every item on its own.
+    Each(fn(&mut Value)),
+    /// Upgrades the items together, for changes that refer from one item to another.
+    All(fn(&mut [Value])),
+}
+
+/// `TASK_MIGRATIONS[n]` upgrades tasks from version `n` to `n + 1`.
+const TASK_MIGRATIONS: &[Migration] = &[Each(task_v0_to_v1), Each(task_v1_to_v2), Each(add_tags), Each(add_priority), Each(add_depends_on), Each(add_checklist), Each(add_notes), Each(task_v7_to_v8), Each(add_scheduled), Each(add_due_time), Each(add_uuid), Each(add_udas), All(depends_on_uuids)];
+
+/// `TEMPLATE_MIGRATIONS[n]` upgrades templates from version `n` to `n + 1`.
+const TEMPLATE_MIGRATIONS: &[Migration] = &[Each(no_change), Each(no_change), Each(add_tags), Each(add_priority), Each(no_change), Each(add_checklist), Each(no_change), Each(no_change), Each(no_change), Each(no_change), Each(no_change), Each(add_udas), Each(no_change)];
+
+const _: () = assert!(TASK_MIGRATIONS.len() == CURRENT_VERSION as usize);
+const _: () = assert!(TEMPLATE_MIGRATIONS.len() == CURRENT_VERSION as usize);
+
+/// The kinds of versioned collections taskust stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    Tasks,
+    Templates,
+    Archive,
+}
+
+impl Collection {
+    /// Returns the key holding the items inside a versioned envelope.
+    pub fn key(self) -> &'static str {
+        match self {
+            Collection::Tasks | Collection::Archive => "tasks",
+            Collection::Templates => "templates",
+        }
+    }
+
+    fn migrations(self) -> &'static [Migration] {
+        match self {
+            Collection::Tasks | Collection::Archive => TASK_MIGRATIONS,
+            Collection::Templates => TEMPLATE_MIGRATIONS,
+        }
+    }
+}
+
+/// Wraps `items` in a versioned envelope: `{"version": N, "<key>": [...]}`.
+pub fn wrap(collection: Collection, items: Value) -> Value {
+    let mut envelope = Map::new();
+    envelope.insert("version".to_string(), Value::from(CURRENT_VERSION));
+    envelope.insert(collection.key().to_string(), items);
+    Value::Object(envelope)
+}
+
+/// Extracts the items from a stored document and upgrades them to [`CURRENT_VERSION`].
+///
+/// A bare JSON array is the unversioned format used before envelopes were introduced
+/// and is treated as version 0. `file` is only used for error messages.
+pub fn unwrap(collection: Collection, document: Value, file: &Path) -> Result<Vec<Value>> {
+    let (version, items) = match document {
+        Value::Array(items) => (0, Value::Array(items)),
+        Value::Object(mut envelope) => {
+            let version = envelope.get("version").and_then(Value::as_u64).ok_or_else(|| {
+                malformed(file, "missing or invalid \"version\" field")
+            })?;
+            let items = envelope.remove(collection.key()).ok_or_else(|| {
+                malformed(file, &format!("missing \"{}\" field", collection.key()))
+            })?;
+            (u32::try_from(version).unwrap_or(u32::MAX), items)
+        }
+        _ => return Err(malformed(file, "expected an array or a versioned object")),
+    };
+
+    let Value::Array(mut items) = items else {
+        return Err(malformed(file, &format!("\"{}\" is not an array", collection.key())));
+    };
+    upgrade(collection, version, &mut items, file)?;
+    Ok(items)
+}
+
+/// Runs the migration chain on `items`, which were stored with format `version`.
+///
+/// Fails with [`TaskustError::UnsupportedVersion`] if `version` is newer than this build.
+pub fn upgrade(collection: Collection, version: u32, items: &mut [Value], file: &Path) -> Result<()> {
+    if version > CURRENT_VERSION {
+        return Err(TaskustError::UnsupportedVersion {
+            file: file.to_path_buf(),
+            version,
+            supported: CURRENT_VERSION,
+        });
+    }
+    for migration in &collection.migrations()[version as usize..] {
+        match migration {
+            Each(f) => items.iter_mut().for_each(f),
+            All(f) => f(items),
+        }
+    }
+    Ok(())
+}
+
+fn malformed(file: &Path, message: &str) -> TaskustError {
+    TaskustError::Parse { file: file.to_path_buf(), line: 0, message: message.to_string() }
+}
+
+fn no_change(_: &mut Value) {}
+
+/// Version 0 files predate `completed`, `hours_worked`, `template`, `recurrence` and
+/// `completed_at` in some or all records; give them explicit values.
+fn task_v0_to_v1(task: &mut Value) {
+    if let Value::Object(t) = task {
+        t.entry("completed").or_insert(Value::Bool(false));
+        t.entry("hours_worked").or_insert(Value::from(0.0));
+        t.entry("template").or_insert(Value::Null);
+        t.entry("recurrence").or_insert(Value::Null);
+        t.entry("completed_at").or_insert(Value::Null);
+    }
+}
+
+/// Version 2 replaces the `hours_worked` total with a `work_log` of dated entries. The
+/// old total becomes a single entry on the day the task was completed, or created if it
+/// is still open, since the actual days are unknown.
+fn task_v1_to_v2(task: &mut Value) {
+    if let Value::Object(t) = task {
+        let hours = t.remove("hours_worked").and_then(|h| h.as_f64()).unwrap_or(0.0);
+        let mut log = Vec::new();
+        if hours != 0.0 {
+            let date = ["completed_at", "created_at"]
+                .iter()
+                .filter_map(|k| t.get(*k).and_then(Value::as_str))
+                .find_map(|ts| ts.get(..10).map(str::to_string));
+            if let Some(date) = date {
+                log.push(serde_json::json!({
+                    "date": date,
+                    "hours": hours,
+                    "note": "Total logged before per-day tracking",
+                }));
+            }
+        }
+        t.entry("work_log").or_insert(Value::Array(log));
+    }
+}
+
+/// Version 3 adds `tags` to tasks and templates.
+fn add_tags(item: &mut Value) {
+    if let Value::Object(i) = item {
+        i.entry("tags").or_insert(Value::Array(Vec::new()));
+    }
+}
+
+/// Version 4 adds `priority` to tasks and templates.
+fn add_priority(item: &mut Value) {
+    if let Value::Object(i) = item {
+        i.entry("priority").or_insert(Value::from("none"));
+    }
+}
+
+/// Version 5 adds `depends_on` to tasks.
+fn add_depends_on(task: &mut Value) {
+    if let Value::Object(t) = task {
+        t.entry("depends_on").or_insert(Value::Array(Vec::new()));
+    }
+}
+
+/// Version 6 adds `checklist` to tasks and templates.
+fn add_checklist(item: &mut Value) {
+    if let Value::Object(i) = item {
+        i.entry("checklist").or_insert(Value::Array(Vec::new()));
+    }
+}
+
+/// Version 7 adds `description` and `annotations` to tasks.
+fn add_notes(task: &mut Value) {
+    if let Value::Object(t) = task {
+        t.entry("description").or_insert(Value::Null);
+        t.entry("annotations").or_insert(Value::Array(Vec::new()));
+    }
+}
+
+/// Version 8 replaces the `completed` flag with a `status` and adds `wait_until`.
+fn task_v7_to_v8(task: &mut Value) {
+    if let Value::Object(t) = task {
+        let completed = t.remove("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+        let status = if completed { "done" } else { "pending" };
+        t.entry("status").or_insert(Value::from(status));
+        t.entry("wait_until").or_insert(Value::Null);
+    }
+}
+
+/// Version 9 adds `scheduled` to tasks.
+fn add_scheduled(task: &mut Value) {
+    if let Value::Object(t) = task {
+        t.entry("scheduled").or_insert(Value::Null);
+    }
+}
+
+/// Version 10 adds `due_time` to tasks.
+fn add_due_time(task: &mut Value) {
+    if let Value::Object(t) = task {
+        t.entry("due_time").or_insert(Value::Null);
+    }
+}
+
+/// Version 11 gives every task a `uuid`.
+///
+/// Files are only rewritten on the next change, so the UUID is derived from fields that
+/// never change rather than random: every load before then must agree on it.
+fn add_uuid(task: &mut Value) {
+    if let Value::Object(t) = task {
+        let field = |key| t.get(key).map(Value::to_string).unwrap_or_default();
+        let seed = format!("{}\n{}\n{}", field("id"), field("created_at"), field("name"));
+        t.entry("uuid").or_insert_with(|| Value::from(Uuid::new_v5(&Uuid::NAMESPACE_OID, seed.as_bytes()).to_string()));
+    }
+}
+
+/// Version 12 adds `udas` to tasks and templates.
+fn add_udas(item: &mut Value) {
+    if let Value::Object(t) = item {
+        t.entry("udas").or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Version 13 stores `depends_on` as UUIDs instead of short IDs, which are reused.
+///
+/// IDs are resolved among the tasks in the same file. IDs no task there has any more are
+/// dropped; they already counted as satisfied.
+fn depends_on_uuids(tasks: &mut [Value]) {
+    let uuids: Vec<(Value, Value)> = tasks
+        .iter()
+        .filter_map(|t| Some((t.get("id")?.clone(), t.get("uuid")?.clone())))
+        .collect();
+    for task in tasks.iter_mut() {
+        if let Some(Value::Array(deps)) = task.get_mut("depends_on") {
+            *deps = deps
+                .iter()
+                .filter_map(|dep| uuids.iter().find(|(id, _)| id == dep).map(|(_, uuid)| uuid.clone()))
+                .collect();
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::{Result, TaskustError};
+use crate::models::{Task, Template};
+use super::schema::{self, Collection, CURRENT_VERSION};
+use super::{Backend, Storage};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tasks (
+        id INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        completed INTEGER NOT NULL, -- done or cancelled
+        due_date TEXT NOT NULL,
+        project TEXT,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tasks_open_due ON tasks (completed, due_date);
+    CREATE INDEX IF NOT EXISTS tasks_project ON tasks (project);
+
+    CREATE TABLE IF NOT EXISTS templates (
+        position INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS templates_name ON templates (name);
+
+    CREATE TABLE IF NOT EXISTS archive (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id INTEGER NOT NULL,
+        completed_at TEXT,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS archive_id ON archive (id);
+";
+
+/// Stores tasks, templates and the archive in a single SQLite database.
+///
+/// Each row keeps the full JSON form of its record in a `data` column, so nothing is lost
+/// when converting to and from [`super::JsonStorage`]. Frequently filtered fields are
+/// duplicated into indexed columns, and archiving is a plain insert instead of a rewrite.
+/// The format version of the `data` column is kept in `PRAGMA user_version`.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database at `path`, upgrading stored rows to the current format.
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStorage> {
+        let path = path.as_ref().to_path_buf();
+        let mut conn = Connection::open(&path)?;
+        conn.execute_batch(SCHEMA)?;
+        migrate(&mut conn, &path)?;
+        Ok(SqliteStorage { path, conn: Mutex::new(conn) })
+    }
+
+    /// Returns the path to the database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn load_rows<T: DeserializeOwned>(&self, sql: &str) -> Result<Vec<T>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut items = Vec::new();
+        for data in rows {
+            items.push(self.decode(&data?)?);
+        }
+        Ok(items)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &str) -> Result<T> {
+        serde_json::from_str(data).map_err(|e| TaskustError::Parse {
+            file: self.path.clone(),
+            line: e.line(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Runs the [`schema`] migration chain over every stored row.
+fn migrate(conn: &mut Connection, path: &Path) -> Result<()> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version == CURRENT_VERSION {
+        return Ok(());
+    }
+    let tables = [
+        ("tasks", "id", Collection::Tasks),
+        ("templates", "position", Collection::Templates),
+        ("archive", "seq", Collection::Archive),
+    ];
+
+    let tx = conn.transaction()?;
+    for (table, key, collection) in tables {
+        let rows: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare(&format!("SELECT {key}, data FROM {table}"))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let mut items = rows
+            .iter()
+            .map(|(_, data)| serde_json::from_str(data))
+            .collect::<serde_json::Result<Vec<serde_json::Value>>>()
+            .map_err(|e| TaskustError::Parse { file: path.to_path_buf(), line: e.line(), message: e.to_string() })?;
+        schema::upgrade(collection, version, &mut items, path)?;
+
+        let mut stmt = tx.prepare(&format!("UPDATE {table} SET data = ?1 WHERE {key} = ?2"))?;
+        for ((key, _), item) in rows.iter().zip(&items) {
+            stmt.execute(params![encode(item)?, key])?;
+        }
+    }
+    tx.pragma_update(None, "user_version", CURRENT_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| TaskustError::Database(e.to_string()))
+}
+
+impl Storage for SqliteStorage {
+    fn backend(&self) -> Backend {
+        Backend::Sqlite
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>> {
+        self.load_rows("SELECT data FROM tasks ORDER BY position")
+    }
+
+    fn save_tasks(&self, tasks: &[Task]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM tasks", [])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO tasks (id, position, completed, due_date, project, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (i, t) in tasks.iter().enumerate() {
+                stmt.execute(params![
+                    t.id as i64,
+                    i as i64,
+                    t.is_closed(),
+                    t.due_date.to_string(),
+                    t.project,
+                    encode(t)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Updates a single row in place instead of rewriting the whole table.
+    fn save_task(&self, task: &Task) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO tasks (id, position, completed, due_date, project, data)
+             VALUES (?1, (SELECT COALESCE(MAX(position), -1) + 1 FROM tasks), ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET
+                completed = excluded.completed,
+                due_date = excluded.due_date,
+                project = excluded.project,
+                data = excluded.data",
+            params![
+                task.id as i64,
+                task.is_closed(),
+                task.due_date.to_string(),
+                task.project,
+                encode(task)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_templates(&self) -> Result<Vec<Template>> {
+        self.load_rows("SELECT data FROM templates ORDER BY position")
+    }
+
+    fn save_templates(&self, templates: &[Template]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM templates", [])?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO templates (position, name, data) VALUES (?1, ?2, ?3)")?;
+            for (i, t) in templates.iter().enumerate() {
+                stmt.execute(params![i as i64, t.name, encode(t)?])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_archived_tasks(&self) -> Result<Vec<Task>> {
+        self.load_rows("SELECT data FROM archive ORDER BY seq")
+    }
+
+    fn append_to_archive(&self, tasks: Vec<Task>) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO archive (id, completed_at, data) VALUES (?1, ?2, ?3)")?;
+            for t in &tasks {
+                stmt.execute(params![t.id as i64, t.completed_at, encode(t)?])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn pop_archive(&self, count: usize) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM archive WHERE seq IN (SELECT seq FROM archive ORDER BY seq DESC LIMIT ?1)",
+            params![count as i64],
+        )?;
+        Ok(())
+    }
+
+    fn delete_database(&self) -> Result<()> {
+        self.conn().execute_batch("DELETE FROM tasks; DELETE FROM templates;")?;
+        Ok(())
+    }
+
+    fn destroy(&self) -> Result<()> {
+        self.conn().execute_batch("DELETE FROM tasks; DELETE FROM templates; DELETE FROM archive;")?;
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::clock::Clock;
+use crate::error::{Result, TaskustError};
+use crate::models::Task;
+
+/// A running timer, stored in `timer.json` in the data directory while active.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Timer {
+    /// UUID of the task being timed. Short IDs are reused after a task is removed or
+    /// archived, so the timer never logs to a task that took over the ID.
+    pub task_uuid: Uuid,
+    /// Timestamp when the timer was started (ISO 8601).
+    pub started_at: String,
+}
+
+impl Timer {
+    /// Starts a timer for `task` at the current time of `clock`.
+    pub fn start(task: &Task, clock: &Clock) -> Timer {
+        Timer { task_uuid: task.uuid, started_at: clock.timestamp() }
+    }
+
+    /// Returns the timed task among `tasks`, or `None` if it has been removed or archived.
+    pub fn task<'a>(&self, tasks: &'a [Task]) -> Option<&'a Task> {
+        tasks.iter().find(|t| t.uuid == self.task_uuid)
+    }
+
+    /// Returns the start time, or `None` if the stored timestamp is malformed.
+    pub fn started(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.started_at).ok()
+    }
+
+    /// Returns the time elapsed since the timer was started, as of the current time of `clock`.
+    pub fn elapsed(&self, clock: &Clock) -> Duration {
+        self.started()
+            .map(|start| clock.now_utc().signed_duration_since(start))
+            .unwrap_or_else(Duration::zero)
+            .max(Duration::zero())
+    }
+
+    /// Loads the running timer from `path`, if any.
+    pub fn load(path: &Path) -> Result<Option<Timer>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let s = fs::read_to_string(path)?;
+        serde_json::from_str(&s).map(Some).map_err(|e| TaskustError::Parse {
+            file: path.to_path_buf(),
+            line: e.line(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Formats a duration as hours and minutes, e.g. `1h 05m`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    format!("{}h {:02}m", elapsed.num_hours(), elapsed.num_minutes() % 60)
+}
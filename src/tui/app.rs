@@ -1,8 +1,15 @@
 use ratatui::widgets::TableState;
-use crate::models::{Task, Template};
-use crate::storage::{load_tasks, save_tasks, load_templates};
-use crate::urgency::compute_urgency;
-use crate::commands::{cmd_complete, cmd_add, cmd_edit, cmd_log, cmd_estimate, cmd_template_add, cmd_template_remove};
+use crate::config::Config;
+use crate::deps;
+use crate::error::{Result, TaskustError};
+use crate::history::HistoryEntry;
+use crate::timer::Timer;
+use crate::models::{ChecklistItem, Task, Template};
+use crate::storage::TaskStore;
+use crate::urgency::{self, sort_by_urgency, UrgencyStrategy};
+use std::sync::Arc;
+use crate::editor::edit_text;
+use crate::commands::{cmd_complete, cmd_cancel, cmd_wait, cmd_add, cmd_annotate, cmd_item_add, cmd_item_check, cmd_item_remove, cmd_edit, cmd_log, cmd_estimate, cmd_remove, cmd_template_add, cmd_template_remove, cmd_undo, cmd_redo, cmd_start, cmd_stop, format_due, format_scheduled, format_tags, parse_ids, NewTask, TaskEdit, TaskFilter};
 use std::collections::HashSet;
 
 #[derive(PartialEq)]
@@ -22,20 +29,41 @@ pub enum InputField {
     Name,
     Project,
     Due,
+    Scheduled,
     Hours,
     Recur,
     Template,
+    Tags,
+    DependsOn,
+    NewItem,
+    Annotation,
+    WaitUntil,
+    Filter,
     LogHours,
     EstimateHours,
 }
 
+/// How the task list is grouped.
+#[derive(PartialEq)]
+pub enum GroupBy {
+    None,
+    Project,
+    /// Tasks with several tags appear under each of them.
+    Tag,
+}
+
 pub enum DisplayItem {
-    Task(Task),
-    ProjectHeader(String, usize), // Name, count
+    Task(Box<Task>),
+    GroupHeader(String, usize), // Name, count
+    ChecklistItem(u64, usize, ChecklistItem), // Task ID, index, item
 }
 
 pub struct App {
+    pub store: TaskStore,
+    /// Tasks shown in the task view, sorted by urgency.
     pub tasks: Vec<Task>,
+    /// Every stored task, including hidden ones, for dependency lookups.
+    pub all_tasks: Vec<Task>,
     pub display_items: Vec<DisplayItem>,
     pub templates: Vec<Template>,
     pub state: TableState,
@@ -48,8 +76,29 @@ pub struct App {
     // For adding tasks, we need to store partial data
     pub add_state: AddState,
     pub show_completed: bool,
-    pub group_by_project: bool,
-    pub expanded_projects: HashSet<String>,
+    pub group_by: GroupBy,
+    pub expanded_groups: HashSet<String>,
+    /// Tasks whose checklist items are shown below them.
+    pub expanded_tasks: HashSet<u64>,
+    /// Task that has open checklist items and is completed by pressing Space again.
+    pub confirm_complete: Option<u64>,
+    /// Only tasks matching this filter are shown.
+    pub filter: TaskFilter,
+    /// Error from the last action, shown in the status bar until the next key press.
+    pub status_message: Option<String>,
+    pub show_history: bool,
+    /// Whether the pane with the selected task's description and annotations is open.
+    pub show_details: bool,
+    /// Whether the popup breaking down the selected task's urgency is open.
+    pub show_explain: bool,
+    /// Recorded changes to the selected task, loaded while the history pane is open.
+    pub history: Vec<HistoryEntry>,
+    /// The running timer, shown in the status bar.
+    pub timer: Option<Timer>,
+    /// Settings from `config.json`, loaded at startup.
+    pub config: Config,
+    /// The urgency strategy tasks are sorted by, named in `config`.
+    pub strategy: Arc<dyn UrgencyStrategy>,
 }
 
 /// State for the multi-step "Add Task" wizard.
@@ -65,31 +114,18 @@ pub struct AddState {
 }
 
 impl App {
-    /// Creates a new App instance and loads initial data.
-    pub fn new() -> App {
-        let mut tasks = load_tasks();
-        // Filter out completed tasks for the main view
-        tasks.retain(|t| !t.completed);
-        // Sort by urgency
-        tasks.sort_by(|a, b| compute_urgency(b).partial_cmp(&compute_urgency(a)).unwrap());
-        
-        let mut state = TableState::default();
-        if !tasks.is_empty() {
-            state.select(Some(0));
-        }
-
-        let templates = load_templates();
-        let mut template_state = TableState::default();
-        if !templates.is_empty() {
-            template_state.select(Some(0));
-        }
-
-        let mut app = App { 
-            tasks, 
+    /// Creates a new App instance backed by `store` and loads initial data.
+    pub fn new(store: TaskStore) -> Result<App> {
+        let config = store.load_config()?;
+        let strategy = urgency::strategy(&config.urgency.strategy)?;
+        let mut app = App {
+            store,
+            tasks: Vec::new(),
+            all_tasks: Vec::new(),
             display_items: Vec::new(),
-            templates,
-            state,
-            template_state,
+            templates: Vec::new(),
+            state: TableState::default(),
+            template_state: TableState::default(),
             view_mode: ViewMode::Tasks,
             input_mode: InputMode::Normal,
             input_field: InputField::None,
@@ -97,11 +133,27 @@ impl App {
             target_id: None,
             add_state: AddState::default(),
             show_completed: false,
-            group_by_project: false,
-            expanded_projects: HashSet::new(),
+            group_by: GroupBy::None,
+            expanded_groups: HashSet::new(),
+            expanded_tasks: HashSet::new(),
+            confirm_complete: None,
+            filter: TaskFilter::default(),
+            status_message: None,
+            show_history: false,
+            show_details: false,
+            show_explain: false,
+            history: Vec::new(),
+            timer: None,
+            config,
+            strategy,
         };
-        app.reload();
-        app
+        app.reload()?;
+        Ok(app)
+    }
+
+    /// Records the outcome of an action so errors can be shown in the status bar.
+    fn report(&mut self, result: Result<()>) {
+        self.status_message = result.err().map(|e| e.to_string());
     }
 
     /// Selects the next item in the current list.
@@ -136,6 +188,8 @@ impl App {
                 self.template_state.select(Some(i));
             }
         }
+        let result = self.load_history();
+        self.report(result);
     }
 
     /// Selects the previous item in the current list.
@@ -170,19 +224,94 @@ impl App {
                 self.template_state.select(Some(i));
             }
         }
+        let result = self.load_history();
+        self.report(result);
+    }
+
+    /// Returns the ID of the selected task, if a task rather than a group header is selected.
+    pub fn selected_task_id(&self) -> Option<u64> {
+        match self.state.selected().and_then(|i| self.display_items.get(i)) {
+            Some(DisplayItem::Task(t)) => Some(t.id),
+            _ => None,
+        }
+    }
+
+    /// Returns the selected task, or the task owning the selected checklist item.
+    pub fn selected_task(&self) -> Option<&Task> {
+        match self.state.selected().and_then(|i| self.display_items.get(i)) {
+            Some(DisplayItem::Task(t)) => Some(t),
+            Some(DisplayItem::ChecklistItem(id, ..)) => self.all_tasks.iter().find(|t| t.id == *id),
+            _ => None,
+        }
+    }
+
+    /// Toggles the pane showing the selected task's description and annotations.
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+
+    /// Toggles the popup showing how the selected task's urgency is calculated.
+    pub fn toggle_explain(&mut self) {
+        self.show_explain = !self.show_explain;
+    }
+
+    /// Opens the selected task's description in the user's editor and saves the result.
+    ///
+    /// The caller must release the terminal first, see `tui::run_app`.
+    pub fn edit_description(&mut self) {
+        if let ViewMode::Templates = self.view_mode { return; }
+        let Some(task) = self.selected_task() else { return };
+        let id = task.id;
+        let current = task.description.clone().unwrap_or_default();
+        let result = edit_text(&current)
+            .and_then(|d| cmd_edit(&self.store, id, TaskEdit { description: Some(d), ..Default::default() }, true))
+            .and_then(|_| self.reload());
+        self.report(result);
     }
 
-    /// Marks the currently selected task as complete.
+    /// Toggles the history pane for the selected task.
+    pub fn toggle_history(&mut self) {
+        self.show_history = !self.show_history;
+        let result = self.load_history();
+        self.report(result);
+    }
+
+    /// Loads the history of the selected task if the history pane is open.
+    fn load_history(&mut self) -> Result<()> {
+        self.history = match (self.show_history, self.selected_task()) {
+            (true, Some(task)) => self.store.load_history(task.id, Some(task.uuid))?,
+            _ => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Marks the currently selected task as complete, or toggles the selected checklist item.
+    ///
+    /// A task with open checklist items is only completed when this is called twice in a row.
     pub fn complete_selected(&mut self) {
         if let ViewMode::Templates = self.view_mode { return; }
         if let Some(i) = self.state.selected() {
             if i < self.display_items.len() {
-                if let DisplayItem::Task(t) = &self.display_items[i] {
-                    let id = t.id;
-                    // Use the command logic to handle recurrence
-                    cmd_complete(id, true);
-                    // Reload tasks
-                    self.reload();
+                match &self.display_items[i] {
+                    DisplayItem::Task(t) => {
+                        let id = t.id;
+                        let open = t.open_items();
+                        if open > 0 && self.confirm_complete.take() != Some(id) {
+                            self.confirm_complete = Some(id);
+                            self.status_message = Some(format!(
+                                "Task {} has {} open checklist item(s). Press Space again to complete it anyway.", id, open
+                            ));
+                            return;
+                        }
+                        // Use the command logic to handle recurrence
+                        let result = cmd_complete(&self.store, id, true, true).and_then(|_| self.reload());
+                        self.report(result);
+                    }
+                    DisplayItem::ChecklistItem(id, index, item) => {
+                        let result = cmd_item_check(&self.store, *id, index + 1, !item.done, true).and_then(|_| self.reload());
+                        self.report(result);
+                    }
+                    DisplayItem::GroupHeader(..) => {}
                 }
             }
         }
@@ -194,14 +323,13 @@ impl App {
             ViewMode::Tasks => {
                 if let Some(i) = self.state.selected() {
                     if i < self.display_items.len() {
-                        if let DisplayItem::Task(t) = &self.display_items[i] {
-                            let id = t.id;
-                            // Direct deletion logic since cmd_remove prints
-                            let mut all_tasks = load_tasks();
-                            all_tasks.retain(|t| t.id != id);
-                            let _ = save_tasks(&all_tasks);
-                            self.reload();
-                        }
+                        let result = match &self.display_items[i] {
+                            DisplayItem::Task(t) => cmd_remove(&self.store, t.id, true),
+                            DisplayItem::ChecklistItem(id, index, _) => cmd_item_remove(&self.store, *id, index + 1, true),
+                            DisplayItem::GroupHeader(..) => return,
+                        };
+                        let result = result.and_then(|_| self.reload());
+                        self.report(result);
                     }
                 }
             }
@@ -209,58 +337,70 @@ impl App {
                 if let Some(i) = self.template_state.selected() {
                     if i < self.templates.len() {
                         let name = self.templates[i].name.clone();
-                        cmd_template_remove(name, true);
-                        self.reload();
+                        let result = cmd_template_remove(&self.store, name, true).and_then(|_| self.reload());
+                        self.report(result);
                     }
                 }
             }
         }
     }
 
+    /// Starts the timer on the selected task, or stops it if it is already running there.
+    pub fn toggle_timer(&mut self) {
+        if let ViewMode::Templates = self.view_mode { return; }
+        let Some(id) = self.selected_task_id() else { return; };
+        let timed = self.timer.as_ref().and_then(|t| t.task(&self.all_tasks)).map(|t| t.id);
+        let result = if timed == Some(id) {
+            cmd_stop(&self.store, true)
+        } else {
+            cmd_start(&self.store, id, true)
+        };
+        let result = result.and_then(|_| self.reload());
+        self.report(result);
+    }
+
+    /// Raises the priority of the selected task one step, wrapping from High back to None.
+    pub fn cycle_priority(&mut self) {
+        if let ViewMode::Templates = self.view_mode { return; }
+        let Some(task) = self.state.selected().and_then(|i| self.display_items.get(i)) else { return; };
+        let DisplayItem::Task(t) = task else { return; };
+        let edit = TaskEdit { priority: Some(t.priority.cycle()), ..Default::default() };
+        let result = cmd_edit(&self.store, t.id, edit, true).and_then(|_| self.reload());
+        self.report(result);
+    }
+
+    /// Reverts the last change, including changes made outside the TUI.
+    pub fn undo(&mut self) {
+        let result = cmd_undo(&self.store, true).and_then(|_| self.reload());
+        self.report(result);
+    }
+
+    /// Reapplies the last undone change.
+    pub fn redo(&mut self) {
+        let result = cmd_redo(&self.store, true).and_then(|_| self.reload());
+        self.report(result);
+    }
+
     /// Reloads tasks and templates from storage and refreshes the display list.
-    pub fn reload(&mut self) {
-        let mut tasks = load_tasks();
-        if !self.show_completed {
-            tasks.retain(|t| !t.completed);
-        }
-        tasks.sort_by(|a, b| compute_urgency(b).partial_cmp(&compute_urgency(a)).unwrap());
+    pub fn reload(&mut self) -> Result<()> {
+        self.timer = self.store.load_timer()?;
+        self.all_tasks = self.store.load_tasks()?;
+        let all = &self.all_tasks;
+        let mut tasks = all.clone();
+        let clock = self.store.clock();
+        tasks.retain(|t| self.filter.shows(t, self.show_completed, clock.today()));
+        sort_by_urgency(&mut tasks, all, self.strategy.as_ref(), &self.config.urgency, &clock);
         self.tasks = tasks;
 
         self.display_items.clear();
-        if self.group_by_project {
-            let mut projects: Vec<Option<String>> = self.tasks.iter()
-                .map(|t| t.project.clone())
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .collect();
-            // Sort projects: None (No Project) first, then alphabetical
-            projects.sort_by(|a, b| {
-                match (a, b) {
-                    (None, None) => std::cmp::Ordering::Equal,
-                    (None, Some(_)) => std::cmp::Ordering::Less,
-                    (Some(_), None) => std::cmp::Ordering::Greater,
-                    (Some(a), Some(b)) => a.cmp(b),
-                }
-            });
-
-            for proj in projects {
-                let proj_name = proj.clone().unwrap_or_else(|| "No Project".to_string());
-                let project_tasks: Vec<&Task> = self.tasks.iter().filter(|t| t.project == proj).collect();
-                
-                if !project_tasks.is_empty() {
-                    self.display_items.push(DisplayItem::ProjectHeader(proj_name.clone(), project_tasks.len()));
-                    
-                    if self.expanded_projects.contains(&proj_name) {
-                        for t in project_tasks {
-                            self.display_items.push(DisplayItem::Task(t.clone()));
-                        }
-                    }
+        match self.group_by {
+            GroupBy::Project => self.group_by_project(),
+            GroupBy::Tag => self.group_by_tag(),
+            GroupBy::None => {
+                for t in &self.tasks {
+                    push_task(&mut self.display_items, &self.expanded_tasks, t);
                 }
             }
-        } else {
-            for t in &self.tasks {
-                self.display_items.push(DisplayItem::Task(t.clone()));
-            }
         }
 
         if self.display_items.is_empty() {
@@ -273,7 +413,7 @@ impl App {
             self.state.select(Some(0));
         }
 
-        self.templates = load_templates();
+        self.templates = self.store.load_templates()?;
         if self.templates.is_empty() {
             self.template_state.select(None);
         } else if let Some(i) = self.template_state.selected() {
@@ -283,12 +423,66 @@ impl App {
         } else {
             self.template_state.select(Some(0));
         }
+        self.load_history()
+    }
+
+    /// Fills the display list with one collapsible group per project.
+    fn group_by_project(&mut self) {
+        let mut projects: Vec<Option<String>> = self.tasks.iter()
+            .map(|t| t.project.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        // Sort projects: None (No Project) first, then alphabetical
+        projects.sort_by(|a, b| {
+            match (a, b) {
+                (None, None) => std::cmp::Ordering::Equal,
+                (None, Some(_)) => std::cmp::Ordering::Less,
+                (Some(_), None) => std::cmp::Ordering::Greater,
+                (Some(a), Some(b)) => a.cmp(b),
+            }
+        });
+
+        for proj in projects {
+            let proj_name = proj.clone().unwrap_or_else(|| "No Project".to_string());
+            let project_tasks: Vec<&Task> = self.tasks.iter().filter(|t| t.project == proj).collect();
+            push_group(&mut self.display_items, &self.expanded_groups, &self.expanded_tasks, proj_name, project_tasks);
+        }
+    }
+
+    /// Fills the display list with one collapsible group per tag.
+    ///
+    /// Tasks with several tags appear in each of their groups; untagged tasks come first.
+    fn group_by_tag(&mut self) {
+        let mut tags: Vec<&String> = self.tasks.iter()
+            .flat_map(|t| &t.tags)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        tags.sort();
+
+        let untagged: Vec<&Task> = self.tasks.iter().filter(|t| t.tags.is_empty()).collect();
+        push_group(&mut self.display_items, &self.expanded_groups, &self.expanded_tasks, "No Tags".to_string(), untagged);
+        for tag in tags {
+            let tag_tasks: Vec<&Task> = self.tasks.iter().filter(|t| t.has_tag(tag)).collect();
+            push_group(&mut self.display_items, &self.expanded_groups, &self.expanded_tasks, format!("+{}", tag), tag_tasks);
+        }
     }
 
-    /// Toggles the visibility of completed tasks.
+    /// Cancels the currently selected task.
+    pub fn cancel_selected(&mut self) {
+        if let ViewMode::Templates = self.view_mode { return; }
+        if let Some(id) = self.selected_task_id() {
+            let result = cmd_cancel(&self.store, id, true).and_then(|_| self.reload());
+            self.report(result);
+        }
+    }
+
+    /// Toggles the visibility of done and cancelled tasks.
     pub fn toggle_completed(&mut self) {
         self.show_completed = !self.show_completed;
-        self.reload();
+        let result = self.reload();
+        self.report(result);
     }
 
     /// Toggles between Task and Template views.
@@ -299,26 +493,40 @@ impl App {
         };
     }
 
-    /// Toggles grouping tasks by project.
+    /// Cycles between no grouping, grouping by project and grouping by tag.
     pub fn toggle_group_view(&mut self) {
-        self.group_by_project = !self.group_by_project;
-        self.reload();
+        self.group_by = match self.group_by {
+            GroupBy::None => GroupBy::Project,
+            GroupBy::Project => GroupBy::Tag,
+            GroupBy::Tag => GroupBy::None,
+        };
+        let result = self.reload();
+        self.report(result);
     }
 
-    /// Toggles the expansion of a project group.
-    pub fn toggle_project_expansion(&mut self) {
+    /// Toggles the expansion of a project or tag group, or of a task's checklist.
+    pub fn toggle_expansion(&mut self) {
         if let ViewMode::Tasks = self.view_mode {
             if let Some(i) = self.state.selected() {
                 if i < self.display_items.len() {
-                    if let DisplayItem::ProjectHeader(name, _) = &self.display_items[i] {
-                        if self.expanded_projects.contains(name) {
-                            self.expanded_projects.remove(name);
-                        } else {
-                            self.expanded_projects.insert(name.clone());
+                    match &self.display_items[i] {
+                        DisplayItem::GroupHeader(name, _) => {
+                            if self.expanded_groups.contains(name) {
+                                self.expanded_groups.remove(name);
+                            } else {
+                                self.expanded_groups.insert(name.clone());
+                            }
+                        }
+                        DisplayItem::Task(t) if !t.checklist.is_empty() => {
+                            if !self.expanded_tasks.remove(&t.id) {
+                                self.expanded_tasks.insert(t.id);
+                            }
                         }
-                        self.reload();
-                        self.state.select(Some(i)); 
+                        _ => return,
                     }
+                    let result = self.reload();
+                    self.report(result);
+                    self.state.select(Some(i));
                 }
             }
         }
@@ -348,6 +556,14 @@ impl App {
         }
     }
 
+    /// Initiates editing of the tag filter, pre-filled with the current one.
+    pub fn start_filter(&mut self) {
+        if let ViewMode::Templates = self.view_mode { return; }
+        self.input_mode = InputMode::Editing;
+        self.input_field = InputField::Filter;
+        self.input_buffer = self.filter.to_string();
+    }
+
     /// Initiates editing of a specific field for the selected task.
     pub fn start_edit(&mut self, field: InputField) {
         if let ViewMode::Templates = self.view_mode { return; }
@@ -363,10 +579,20 @@ impl App {
                     match self.input_field {
                         InputField::Name => self.input_buffer = t.name.clone(),
                         InputField::Project => self.input_buffer = t.project.clone().unwrap_or_default(),
-                        InputField::Due => self.input_buffer = t.due_date.to_string(),
+                        InputField::Due => self.input_buffer = format_due(t),
+                        InputField::Scheduled => self.input_buffer = format_scheduled(t.scheduled),
                         InputField::Hours => self.input_buffer = t.expected_hours.to_string(),
                         InputField::Recur => self.input_buffer = t.recurrence.clone().unwrap_or_default(),
                         InputField::Template => self.input_buffer = t.template.clone().unwrap_or_default(),
+                        InputField::Tags => self.input_buffer = format_tags(&t.tags),
+                        InputField::NewItem | InputField::Annotation => self.input_buffer = String::new(),
+                        InputField::WaitUntil => {
+                            self.input_buffer = t.wait_until.map(|d| d.to_string()).unwrap_or_default();
+                        }
+                        InputField::DependsOn => {
+                            let ids = deps::dependency_ids(t, &self.all_tasks);
+                            self.input_buffer = ids.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+                        }
                         InputField::LogHours => self.input_buffer = String::new(),
                         InputField::EstimateHours => self.input_buffer = String::new(),
                         _ => {}
@@ -390,37 +616,33 @@ impl App {
         if let Some(tmpl_name) = &self.add_state.template {
             // Adding task from template
             match self.add_state.step {
-                0 => { // Name
-                    if !self.input_buffer.is_empty() {
-                        self.add_state.name = self.input_buffer.clone();
-                        self.add_state.step += 1;
-                        self.input_buffer.clear();
-                    }
+                0 if !self.input_buffer.is_empty() => { // Name
+                    self.add_state.name = self.input_buffer.clone();
+                    self.add_state.step += 1;
+                    self.input_buffer.clear();
                 }
-                1 => { // Due
-                    if !self.input_buffer.is_empty() {
-                        self.add_state.due = self.input_buffer.clone();
-                        self.add_state.step += 1;
-                        self.input_buffer.clear();
-                    }
+                1 if !self.input_buffer.is_empty() => { // Due
+                    self.add_state.due = self.input_buffer.clone();
+                    self.add_state.step += 1;
+                    self.input_buffer.clear();
                 }
                 2 => { // Recur
                     if !self.input_buffer.is_empty() {
                         self.add_state.recur = Some(self.input_buffer.clone());
                     }
                     // Finish Add
-                    cmd_add(
-                        self.add_state.name.clone(),
-                        None, // Project from template
-                        None, // Hours from template
-                        self.add_state.due.clone(),
-                        Some(tmpl_name.clone()),
-                        self.add_state.recur.clone(),
-                        true
-                    );
+                    // Project, hours and tags come from the template
+                    let new = NewTask {
+                        name: self.add_state.name.clone(),
+                        due: self.add_state.due.clone(),
+                        template: Some(tmpl_name.clone()),
+                        recur: self.add_state.recur.clone(),
+                        ..Default::default()
+                    };
+                    let result = cmd_add(&self.store, new, true).and_then(|_| self.reload());
                     self.input_mode = InputMode::Normal;
                     self.view_mode = ViewMode::Tasks; // Switch back to tasks view
-                    self.reload();
+                    self.report(result);
                 }
                 _ => {}
             }
@@ -428,19 +650,15 @@ impl App {
             match self.view_mode {
                 ViewMode::Tasks => {
                     match self.add_state.step {
-                        0 => { // Name
-                            if !self.input_buffer.is_empty() {
-                                self.add_state.name = self.input_buffer.clone();
-                                self.add_state.step += 1;
-                                self.input_buffer.clear();
-                            }
+                        0 if !self.input_buffer.is_empty() => { // Name
+                            self.add_state.name = self.input_buffer.clone();
+                            self.add_state.step += 1;
+                            self.input_buffer.clear();
                         }
-                        1 => { // Due
-                            if !self.input_buffer.is_empty() {
-                                self.add_state.due = self.input_buffer.clone();
-                                self.add_state.step += 1;
-                                self.input_buffer.clear();
-                            }
+                        1 if !self.input_buffer.is_empty() => { // Due
+                            self.add_state.due = self.input_buffer.clone();
+                            self.add_state.step += 1;
+                            self.input_buffer.clear();
                         }
                         2 => { // Project
                             if !self.input_buffer.is_empty() {
@@ -472,29 +690,28 @@ impl App {
                                 self.add_state.template = Some(self.input_buffer.clone());
                             }
                             // Finish Add
-                            cmd_add(
-                                self.add_state.name.clone(),
-                                self.add_state.project.clone(),
-                                self.add_state.hours,
-                                self.add_state.due.clone(),
-                                self.add_state.template.clone(),
-                                self.add_state.recur.clone(),
-                                true
-                            );
+                            let new = NewTask {
+                                name: self.add_state.name.clone(),
+                                project: self.add_state.project.clone(),
+                                hours: self.add_state.hours,
+                                due: self.add_state.due.clone(),
+                                template: self.add_state.template.clone(),
+                                recur: self.add_state.recur.clone(),
+                                ..Default::default()
+                            };
+                            let result = cmd_add(&self.store, new, true).and_then(|_| self.reload());
                             self.input_mode = InputMode::Normal;
-                            self.reload();
+                            self.report(result);
                         }
                         _ => {}
                     }
                 }
                 ViewMode::Templates => {
                     match self.add_state.step {
-                        0 => { // Name
-                            if !self.input_buffer.is_empty() {
-                                self.add_state.name = self.input_buffer.clone();
-                                self.add_state.step += 1;
-                                self.input_buffer.clear();
-                            }
+                        0 if !self.input_buffer.is_empty() => { // Name
+                            self.add_state.name = self.input_buffer.clone();
+                            self.add_state.step += 1;
+                            self.input_buffer.clear();
                         }
                         1 => { // Project
                             if !self.input_buffer.is_empty() {
@@ -504,20 +721,17 @@ impl App {
                             self.input_buffer.clear();
                         }
                         2 => { // Hours
-                            let hours = if let Ok(h) = self.input_buffer.parse::<f64>() {
-                                h
-                            } else {
-                                1.0
+                            let hours = self.input_buffer.parse::<f64>().unwrap_or(1.0);
+
+                            let template = Template {
+                                name: self.add_state.name.clone(),
+                                project: self.add_state.project.clone(),
+                                default_hours: hours,
+                                ..Default::default()
                             };
-                            
-                            cmd_template_add(
-                                self.add_state.name.clone(),
-                                self.add_state.project.clone(),
-                                hours,
-                                true
-                            );
+                            let result = cmd_template_add(&self.store, template, true).and_then(|_| self.reload());
                             self.input_mode = InputMode::Normal;
-                            self.reload();
+                            self.report(result);
                         }
                         _ => {}
                     }
@@ -526,34 +740,87 @@ impl App {
         }
     }
 
+    /// Replaces the tags of task `id` with the tags in `input`, e.g. `+review +oncall`.
+    fn edit_tags(&self, id: u64, input: &str) -> Result<()> {
+        let current = self.tasks.iter().find(|t| t.id == id).map(|t| t.tags.clone()).unwrap_or_default();
+        let edit = TaskEdit {
+            add_tags: input.split_whitespace().map(String::from).collect(),
+            remove_tags: current,
+            ..Default::default()
+        };
+        cmd_edit(&self.store, id, edit, true)
+    }
+
     /// Handles input for the "Edit Task" mode.
     fn handle_editing_input(&mut self) {
+        if let InputField::Filter = self.input_field {
+            let result = self.store.load_config()
+                .and_then(|config| TaskFilter::parse(&self.input_buffer, &config))
+                .map(|f| self.filter = f);
+            self.input_mode = InputMode::Normal;
+            let result = result.and_then(|_| self.reload());
+            self.report(result);
+            return;
+        }
         if let Some(id) = self.target_id {
-            match self.input_field {
-                InputField::Name => cmd_edit(id, Some(self.input_buffer.clone()), None, None, None, None, None, None, true),
-                InputField::Project => cmd_edit(id, None, Some(self.input_buffer.clone()), None, None, None, None, None, true),
-                InputField::Due => cmd_edit(id, None, None, None, None, None, Some(self.input_buffer.clone()), None, true),
-                InputField::Hours => {
-                    if let Ok(h) = self.input_buffer.parse::<f64>() {
-                        cmd_edit(id, None, None, None, Some(h), None, None, None, true);
-                    }
-                },
-                InputField::Recur => cmd_edit(id, None, None, None, None, None, None, Some(self.input_buffer.clone()), true),
-                InputField::Template => cmd_edit(id, None, None, Some(self.input_buffer.clone()), None, None, None, None, true),
-                InputField::LogHours => {
-                    if let Ok(h) = self.input_buffer.parse::<f64>() {
-                        cmd_log(id, h, true);
-                    }
-                },
-                InputField::EstimateHours => {
-                    if let Ok(h) = self.input_buffer.parse::<f64>() {
-                        cmd_estimate(id, h, true);
-                    }
-                },
-                _ => {}
-            }
+            let input = self.input_buffer.clone();
+            let result = match self.input_field {
+                InputField::Name => cmd_edit(&self.store, id, TaskEdit { name: Some(input), ..Default::default() }, true),
+                InputField::Project => cmd_edit(&self.store, id, TaskEdit { project: Some(input), ..Default::default() }, true),
+                InputField::Due => cmd_edit(&self.store, id, TaskEdit { due: Some(input), ..Default::default() }, true),
+                InputField::Scheduled => cmd_edit(&self.store, id, TaskEdit { scheduled: Some(input.trim().to_string()), ..Default::default() }, true),
+                InputField::Hours => parse_hours(&input)
+                    .and_then(|h| cmd_edit(&self.store, id, TaskEdit { hours: Some(h), ..Default::default() }, true)),
+                InputField::Recur => cmd_edit(&self.store, id, TaskEdit { recur: Some(input), ..Default::default() }, true),
+                InputField::Template => cmd_edit(&self.store, id, TaskEdit { template: Some(input), ..Default::default() }, true),
+                InputField::Tags => self.edit_tags(id, &input),
+                InputField::NewItem => {
+                    self.expanded_tasks.insert(id);
+                    cmd_item_add(&self.store, id, input, true)
+                }
+                InputField::Annotation => cmd_annotate(&self.store, id, input, true),
+                InputField::WaitUntil => {
+                    let until = Some(input.trim().to_string()).filter(|d| !d.is_empty());
+                    cmd_wait(&self.store, id, until, true)
+                }
+                InputField::DependsOn => parse_ids(&input)
+                    .and_then(|d| cmd_edit(&self.store, id, TaskEdit { depends_on: Some(d), ..Default::default() }, true)),
+                InputField::LogHours => parse_hours(&input).and_then(|h| cmd_log(&self.store, id, h, None, None, true)),
+                InputField::EstimateHours => parse_hours(&input).and_then(|h| cmd_estimate(&self.store, id, h, true)),
+                _ => Ok(()),
+            };
             self.input_mode = InputMode::Normal;
-            self.reload();
+            let result = result.and_then(|_| self.reload());
+            self.report(result);
         }
     }
 }
+
+/// Adds a group header for `tasks`, followed by the tasks themselves if the group is expanded.
+fn push_group(items: &mut Vec<DisplayItem>, expanded: &HashSet<String>, expanded_tasks: &HashSet<u64>, name: String, tasks: Vec<&Task>) {
+    if tasks.is_empty() {
+        return;
+    }
+    let is_expanded = expanded.contains(&name);
+    items.push(DisplayItem::GroupHeader(name, tasks.len()));
+    if is_expanded {
+        for t in tasks {
+            push_task(items, expanded_tasks, t);
+        }
+    }
+}
+
+/// Adds a task, followed by its checklist items if the task is expanded.
+fn push_task(items: &mut Vec<DisplayItem>, expanded: &HashSet<u64>, task: &Task) {
+    items.push(DisplayItem::Task(Box::new(task.clone())));
+    if expanded.contains(&task.id) {
+        for (i, item) in task.checklist.iter().enumerate() {
+            items.push(DisplayItem::ChecklistItem(task.id, i, item.clone()));
+        }
+    }
+}
+
+fn parse_hours(input: &str) -> Result<f64> {
+    input.trim().parse::<f64>()
+        .map_err(|_| TaskustError::InvalidArgument(format!("Invalid number of hours '{}'.", input)))
+}
@@ -1,9 +1,10 @@
 pub mod app;
 pub mod ui;
 
-use std::{error::Error, io};
+use std::io;
+use std::time::Duration;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -12,9 +13,15 @@ use ratatui::{
     Terminal,
 };
 use app::{App, InputMode, InputField, ViewMode};
+use crate::error::Result;
+use crate::storage::TaskStore;
 use ui::ui;
 
-pub fn run_tui() -> Result<(), Box<dyn Error>> {
+/// Runs the interactive TUI against the given store until the user quits.
+pub fn run_tui(store: TaskStore) -> Result<()> {
+    // Load data before touching the terminal so storage errors are reported normally
+    let mut app = App::new(store)?;
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -22,9 +29,6 @@ pub fn run_tui() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-    let mut app = App::new();
-
     // Run loop
     let res = run_app(&mut terminal, &mut app);
 
@@ -37,19 +41,45 @@ pub fn run_tui() -> Result<(), Box<dyn Error>> {
     )?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        println!("{:?}", err)
-    }
+    res.map_err(Into::into)
+}
 
-    Ok(())
+/// Returns the terminal to normal mode so another program can use it.
+fn suspend() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+}
+
+/// Takes the terminal back after [`suspend`].
+fn resume() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     loop {
         terminal.draw(|f| ui(f, app))?;
 
+        // Wake up periodically so the running timer in the status bar stays current
+        if !event::poll(Duration::from_secs(1))? {
+            continue;
+        }
         if let Event::Key(key) = event::read()? {
+            app.status_message = None;
+            if key.code != KeyCode::Char(' ') {
+                app.confirm_complete = None;
+            }
             match app.input_mode {
+                InputMode::Normal if key.modifiers.contains(KeyModifiers::CONTROL) => match key.code {
+                    KeyCode::Char('z') => app.undo(),
+                    KeyCode::Char('r') => app.redo(),
+                    _ => {}
+                },
+                InputMode::Normal if app.show_explain => match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Esc | KeyCode::Char('U') => app.toggle_explain(),
+                    _ => {}
+                },
                 InputMode::Normal => match key.code {
                     KeyCode::Char('q') => return Ok(()),
                     KeyCode::Down | KeyCode::Char('j') => app.next(),
@@ -60,17 +90,37 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                     KeyCode::Char('n') => app.start_edit(InputField::Name),
                     KeyCode::Char('p') => app.start_edit(InputField::Project),
                     KeyCode::Char('t') => app.start_edit(InputField::Due), // 't' for Time/Date
+                    KeyCode::Char('S') => app.start_edit(InputField::Scheduled),
                     KeyCode::Char('h') => app.start_edit(InputField::Hours),
                     KeyCode::Char('r') => app.start_edit(InputField::Recur),
                     KeyCode::Char('m') => app.start_edit(InputField::Template),
+                    KeyCode::Char('T') => app.start_edit(InputField::Tags),
+                    KeyCode::Char('P') => app.cycle_priority(),
+                    KeyCode::Char('b') => app.start_edit(InputField::DependsOn),
+                    KeyCode::Char('i') => app.start_edit(InputField::NewItem),
+                    KeyCode::Char('A') => app.start_edit(InputField::Annotation),
+                    KeyCode::Char('E') => {
+                        // Hand the terminal to the editor while it runs
+                        suspend()?;
+                        app.edit_description();
+                        resume()?;
+                        terminal.clear()?;
+                    }
+                    KeyCode::Char('N') => app.toggle_details(),
+                    KeyCode::Char('x') => app.cancel_selected(),
+                    KeyCode::Char('w') => app.start_edit(InputField::WaitUntil),
+                    KeyCode::Char('/') => app.start_filter(),
                     KeyCode::Char('l') => app.start_edit(InputField::LogHours),
+                    KeyCode::Char('s') => app.toggle_timer(),
                     KeyCode::Char('u') => app.start_edit(InputField::EstimateHours), // 'u' for Update
                     KeyCode::Char('c') => app.toggle_completed(),
                     KeyCode::Char('v') => app.toggle_view(),
                     KeyCode::Char('g') => app.toggle_group_view(),
+                    KeyCode::Char('H') => app.toggle_history(),
+                    KeyCode::Char('U') if app.selected_task().is_some() => app.toggle_explain(),
                     KeyCode::Enter => {
                         match app.view_mode {
-                            ViewMode::Tasks => app.toggle_project_expansion(),
+                            ViewMode::Tasks => app.toggle_expansion(),
                             ViewMode::Templates => app.start_add_from_template(),
                         }
                     },
@@ -1,12 +1,16 @@
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap},
     Frame,
 };
-use chrono::Local;
-use crate::urgency::compute_urgency;
-use super::app::{App, InputMode, ViewMode, InputField, DisplayItem};
+use crate::history::{format_timestamp, format_value};
+use crate::timer::format_elapsed;
+use crate::urgency::{is_at_risk, UrgencyLevel};
+use crate::commands::{format_due, format_priority, format_scheduled, format_time_left_at_risk, format_tags, name_with_progress, task_status};
+use crate::models::Priority;
+use super::app::{App, InputMode, ViewMode, InputField, DisplayItem, GroupBy};
 
 /// Renders the main UI loop.
 pub fn ui(f: &mut Frame, app: &mut App) {
@@ -19,17 +23,41 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .split(f.area());
 
     match app.view_mode {
+        ViewMode::Tasks if app.show_history || app.show_details => {
+            let panes = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(chunks[0]);
+            render_tasks(f, app, panes[0]);
+            // Side by side when both are open
+            let bottom = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+                .split(panes[1]);
+            match (app.show_details, app.show_history) {
+                (true, true) => {
+                    render_details(f, app, bottom[0]);
+                    render_history(f, app, bottom[1]);
+                }
+                (true, false) => render_details(f, app, panes[1]),
+                _ => render_history(f, app, panes[1]),
+            }
+        }
         ViewMode::Tasks => render_tasks(f, app, chunks[0]),
         ViewMode::Templates => render_templates(f, app, chunks[0]),
     }
 
     render_help(f, app, chunks[1]);
     render_input_popup(f, app);
+    if app.show_explain {
+        render_explain_popup(f, app);
+    }
 }
 
 /// Renders the task list table.
 fn render_tasks(f: &mut Frame, app: &mut App, area: Rect) {
-    let today = Local::now().date_naive();
+    let clock = app.store.clock();
+    let columns: Vec<(String, String)> = app.config.columns().iter().map(|u| (u.name.clone(), u.label().to_string())).collect();
 
     let rows: Vec<Row> = app
         .display_items
@@ -37,46 +65,44 @@ fn render_tasks(f: &mut Frame, app: &mut App, area: Rect) {
         .map(|item| {
             match item {
                 DisplayItem::Task(t) => {
-                    let urgency = compute_urgency(t);
-                    let days_left = (t.due_date - today).num_days();
-                    let time_left_str = if days_left < 0 {
-                        format!("{}d overdue", days_left.abs())
-                    } else if days_left == 0 {
-                        "Today".to_string()
-                    } else {
-                        format!("{}d", days_left)
-                    };
+                    let urgency = app.strategy.urgency(t, &app.all_tasks, &app.config.urgency, &clock);
 
-                    let style = if urgency > 50.0 {
-                        Style::default().fg(Color::Red)
-                    } else if urgency > 20.0 {
-                        Style::default().fg(Color::Yellow)
-                    } else {
-                        Style::default().fg(Color::Green)
+                    let style = match app.strategy.level(urgency, &app.config.urgency) {
+                        _ if t.is_closed() => Style::default().fg(Color::DarkGray),
+                        UrgencyLevel::High => Style::default().fg(Color::Red),
+                        UrgencyLevel::Medium => Style::default().fg(Color::Yellow),
+                        UrgencyLevel::Low => Style::default().fg(Color::Green),
                     };
                     
-                    let name_display = if app.group_by_project {
-                        format!("  {}", t.name)
+                    let name_display = if app.group_by != GroupBy::None {
+                        format!("  {}", name_with_progress(t))
                     } else {
-                        t.name.clone()
+                        name_with_progress(t)
                     };
 
-                    Row::new(vec![
+                    let mut cells = vec![
                         Cell::from(t.id.to_string()),
                         Cell::from(name_display),
                         Cell::from(t.project.clone().unwrap_or_default()),
+                        Cell::from(format_tags(&t.tags)),
+                        Cell::from(format_priority(t.priority)).style(priority_style(t.priority)),
                         Cell::from(t.template.clone().unwrap_or_default()),
-                        Cell::from(t.due_date.to_string()),
-                        Cell::from(time_left_str),
-                        Cell::from(format!("{:.1}", t.hours_worked)),
+                        Cell::from(format_scheduled(t.scheduled)),
+                        Cell::from(format_due(t)),
+                        Cell::from(format_time_left_at_risk(t, &clock, &app.config.urgency)).style(
+                            if is_at_risk(t, &app.config.urgency, &clock) { Style::default().fg(Color::Red) } else { Style::default() },
+                        ),
+                        Cell::from(format!("{:.1}", t.hours_worked())),
                         Cell::from(format!("{:.1}", t.expected_hours)),
                         Cell::from(format!("{:.1}", urgency)),
-                        Cell::from(if t.completed { "Done" } else { "Pending" }),
-                    ]).style(style)
+                        Cell::from(task_status(t, &app.all_tasks, clock.today())),
+                    ];
+                    cells.extend(columns.iter().map(|(name, _)| Cell::from(t.udas.get(name).cloned().unwrap_or_default())));
+                    Row::new(cells).style(style)
                 },
-                DisplayItem::ProjectHeader(name, count) => {
+                DisplayItem::GroupHeader(name, count) => {
                     let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
-                    let icon = if app.expanded_projects.contains(name) { "▼" } else { "▶" };
+                    let icon = if app.expanded_groups.contains(name) { "▼" } else { "▶" };
                     Row::new(vec![
                         Cell::from(""),
                         Cell::from(format!("{} {} ({})", icon, name, count)),
@@ -88,36 +114,150 @@ fn render_tasks(f: &mut Frame, app: &mut App, area: Rect) {
                         Cell::from(""),
                         Cell::from(""),
                         Cell::from(""),
+                        Cell::from(""),
+                        Cell::from(""),
+                        Cell::from(""),
+                    ]).style(style)
+                }
+                DisplayItem::ChecklistItem(_, _, item) => {
+                    let indent = if app.group_by != GroupBy::None { "    " } else { "  " };
+                    let (mark, style) = if item.done {
+                        ("[x]", Style::default().fg(Color::DarkGray))
+                    } else {
+                        ("[ ]", Style::default())
+                    };
+                    Row::new(vec![
+                        Cell::from(""),
+                        Cell::from(format!("{}{} {}", indent, mark, item.text)),
                     ]).style(style)
                 }
             }
         })
         .collect();
 
-    let widths = [
+    let mut widths = vec![
         Constraint::Length(4),
         Constraint::Min(20),
         Constraint::Length(10),
+        Constraint::Length(14),
+        Constraint::Length(6),
+        Constraint::Length(10),
         Constraint::Length(10),
-        Constraint::Length(12),
-        Constraint::Length(12),
+        Constraint::Length(16),
+        Constraint::Length(18),
         Constraint::Length(6),
         Constraint::Length(6),
         Constraint::Length(6),
         Constraint::Length(8),
     ];
+    widths.extend(columns.iter().map(|(_, label)| Constraint::Length(label.len().max(8) as u16)));
+    let mut header = vec!["ID", "Name", "Project", "Tags", "Pri", "Template", "Scheduled", "Due", "Time Left", "Worked", "Est", "Urg", "Status"];
+    header.extend(columns.iter().map(|(_, label)| label.as_str()));
 
+    let title = if app.filter.is_empty() {
+        "Taskust - Tasks".to_string()
+    } else {
+        format!("Taskust - Tasks [{}]", app.filter)
+    };
     let table = Table::new(rows, widths)
-        .header(Row::new(vec!["ID", "Name", "Project", "Template", "Due", "Time Left", "Worked", "Est", "Urg", "Status"])
+        .header(Row::new(header)
             .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
             .bottom_margin(1))
-        .block(Block::default().borders(Borders::ALL).title("Taskust - Tasks"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .row_highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(Color::DarkGray))
         .highlight_symbol(">> ");
 
     f.render_stateful_widget(table, area, &mut app.state);
 }
 
+/// Colours a priority cell; no priority keeps the row's colour.
+fn priority_style(priority: Priority) -> Style {
+    match priority {
+        Priority::High => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        Priority::Medium => Style::default().fg(Color::Yellow),
+        Priority::Low => Style::default().fg(Color::Blue),
+        Priority::None => Style::default(),
+    }
+}
+
+/// Renders the custom attributes, description and annotations of the selected task.
+fn render_details(f: &mut Frame, app: &App, area: Rect) {
+    let task = app.selected_task();
+    let mut lines: Vec<Line> = Vec::new();
+    if let Some(t) = task {
+        for (name, value) in &t.udas {
+            let label = app.config.uda(name).map(|u| u.label()).unwrap_or(name);
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}: ", label), Style::default().fg(Color::Cyan)),
+                Span::raw(value.clone()),
+            ]));
+        }
+        if !t.udas.is_empty() {
+            lines.push(Line::from(""));
+        }
+        match &t.description {
+            Some(d) => lines.extend(d.lines().map(|l| Line::from(l.to_string()))),
+            None => lines.push(Line::styled("No description (E to edit)", Style::default().fg(Color::DarkGray))),
+        }
+        if !t.annotations.is_empty() {
+            lines.push(Line::from(""));
+        }
+        for a in &t.annotations {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}  ", format_timestamp(&a.timestamp)), Style::default().fg(Color::Cyan)),
+                Span::raw(a.text.clone()),
+            ]));
+        }
+    }
+
+    let title = match task {
+        Some(t) => format!("Details - Task {}", t.id),
+        None => "Details".to_string(),
+    };
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(paragraph, area);
+}
+
+/// Renders the change history of the selected task, newest first.
+fn render_history(f: &mut Frame, app: &App, area: Rect) {
+    let rows: Vec<Row> = app
+        .history
+        .iter()
+        .rev()
+        .map(|e| {
+            Row::new(vec![
+                Cell::from(format_timestamp(&e.timestamp)),
+                Cell::from(e.operation.clone()),
+                Cell::from(e.field.clone()),
+                Cell::from(format_value(&e.old)),
+                Cell::from(format_value(&e.new)),
+            ])
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(16),
+        Constraint::Length(24),
+        Constraint::Length(14),
+        Constraint::Min(10),
+        Constraint::Min(10),
+    ];
+
+    let title = match app.selected_task_id() {
+        Some(id) => format!("History - Task {}", id),
+        None => "History".to_string(),
+    };
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Time", "Change", "Field", "Old", "New"])
+            .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .bottom_margin(1))
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    f.render_widget(table, area);
+}
+
 /// Renders the template list table.
 fn render_templates(f: &mut Frame, app: &mut App, area: Rect) {
     let rows: Vec<Row> = app
@@ -128,6 +268,8 @@ fn render_templates(f: &mut Frame, app: &mut App, area: Rect) {
                 Cell::from(t.name.clone()),
                 Cell::from(t.project.clone().unwrap_or_default()),
                 Cell::from(format!("{:.1}", t.default_hours)),
+                Cell::from(format_tags(&t.tags)),
+                Cell::from(format_priority(t.priority)).style(priority_style(t.priority)),
             ])
         })
         .collect();
@@ -136,10 +278,12 @@ fn render_templates(f: &mut Frame, app: &mut App, area: Rect) {
         Constraint::Min(20),
         Constraint::Length(20),
         Constraint::Length(10),
+        Constraint::Length(20),
+        Constraint::Length(8),
     ];
 
     let table = Table::new(rows, widths)
-        .header(Row::new(vec!["Name", "Project", "Est Hours"])
+        .header(Row::new(vec!["Name", "Project", "Est Hours", "Tags", "Priority"])
             .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
             .bottom_margin(1))
         .block(Block::default().borders(Borders::ALL).title("Taskust - Templates"))
@@ -153,20 +297,39 @@ fn render_templates(f: &mut Frame, app: &mut App, area: Rect) {
 fn render_help(f: &mut Frame, app: &App, area: Rect) {
     let help_text = match app.input_mode {
         InputMode::Normal => match app.view_mode {
-            ViewMode::Tasks => "q: Quit | a: Add | n: Name | p: Proj | t: Due | h: Hrs | r: Recur | m: Tmpl | l: Log | u: Est | c: Toggle Done | Space: Done | d: Del | v: View Templates | g: Group",
-            ViewMode::Templates => "q: Quit | a: Add | v: View Tasks | Enter: Create Task from Template | d: Del",
+            ViewMode::Tasks => "q: Quit | a: Add | n: Name | p: Proj | t: Due | S: Sched | h: Hrs | r: Recur | m: Tmpl | T: Tags | P: Priority | b: Deps | i: Add Item | A: Annotate | E: Description | N: Notes | Enter: Expand | /: Filter | l: Log | s: Timer | u: Est | c: Toggle Closed | Space: Done | x: Cancel | w: Wait | d: Del | v: View Templates | g: Group | H: History | U: Urgency | ^z: Undo | ^r: Redo",
+            ViewMode::Templates => "q: Quit | a: Add | v: View Tasks | Enter: Create Task from Template | d: Del | ^z: Undo | ^r: Redo",
         },
         InputMode::Editing => "Enter: Save | Esc: Cancel",
         InputMode::Adding => "Enter: Next Step | Esc: Cancel",
     };
     
-    let help = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::Gray))
-        .block(Block::default().borders(Borders::ALL));
+    let help = match &app.status_message {
+        Some(msg) => Paragraph::new(msg.as_str()).style(Style::default().fg(Color::Red)),
+        None => Paragraph::new(help_text).style(Style::default().fg(Color::Gray)),
+    }
+    .block(timer_block(app));
     
     f.render_widget(help, area);
 }
 
+/// Returns the status bar block, titled with the running timer if there is one.
+fn timer_block(app: &App) -> Block<'_> {
+    let block = Block::default().borders(Borders::ALL);
+    match &app.timer {
+        Some(timer) => {
+            let task = match timer.task(&app.all_tasks) {
+                Some(t) => format!("task {} ({})", t.id, t.name),
+                None => "removed task".to_string(),
+            };
+            block
+                .title(format!(" Timer: {} - {} ", task, format_elapsed(timer.elapsed(&app.store.clock()))))
+                .title_style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+        }
+        None => block,
+    }
+}
+
 /// Renders the input popup for adding/editing tasks.
 fn render_input_popup(f: &mut Frame, app: &App) {
     match app.input_mode {
@@ -180,7 +343,7 @@ fn render_input_popup(f: &mut Frame, app: &App) {
                     if let Some(tmpl) = &app.add_state.template {
                         title_string = match app.add_state.step {
                             0 => format!("Add Task from '{}': Enter Name", tmpl),
-                            1 => format!("Add Task from '{}': Enter Due Date (YYYY-MM-DD)", tmpl),
+                            1 => format!("Add Task from '{}': Enter Due Date (YYYY-MM-DD [HH:MM])", tmpl),
                             2 => format!("Add Task from '{}': Enter Recurrence (Optional)", tmpl),
                             _ => "Add Task".to_string(),
                         };
@@ -190,7 +353,7 @@ fn render_input_popup(f: &mut Frame, app: &App) {
                             ViewMode::Tasks => {
                                 match app.add_state.step {
                                     0 => "Add Task: Enter Name",
-                                    1 => "Add Task: Enter Due Date (YYYY-MM-DD)",
+                                    1 => "Add Task: Enter Due Date (YYYY-MM-DD [HH:MM])",
                                     2 => "Add Task: Enter Project (Optional)",
                                     3 => "Add Task: Enter Expected Hours",
                                     4 => "Add Task: Enter Recurrence (Optional)",
@@ -213,10 +376,17 @@ fn render_input_popup(f: &mut Frame, app: &App) {
                     match app.input_field {
                         InputField::Name => "Edit Name",
                         InputField::Project => "Edit Project",
-                        InputField::Due => "Edit Due Date (YYYY-MM-DD)",
+                        InputField::Due => "Edit Due Date (YYYY-MM-DD [HH:MM])",
+                        InputField::Scheduled => "Edit Scheduled Date (YYYY-MM-DD, empty to clear)",
                         InputField::Hours => "Edit Expected Hours",
                         InputField::Recur => "Edit Recurrence",
                         InputField::Template => "Edit Template",
+                        InputField::Tags => "Edit Tags (e.g. +review +oncall)",
+                        InputField::DependsOn => "Edit Dependencies (task IDs, e.g. 3,7)",
+                        InputField::NewItem => "Add Checklist Item",
+                        InputField::Annotation => "Add Annotation",
+                        InputField::WaitUntil => "Wait Until (YYYY-MM-DD, empty for no date)",
+                        InputField::Filter => "Filter (e.g. +review -blocked status:waiting)",
                         InputField::LogHours => "Log Hours Worked",
                         InputField::EstimateHours => "Update Estimate (Remaining)",
                         _ => "Edit",
@@ -235,6 +405,32 @@ fn render_input_popup(f: &mut Frame, app: &App) {
     }
 }
 
+/// Renders the popup listing the terms of the selected task's urgency.
+fn render_explain_popup(f: &mut Frame, app: &App) {
+    let Some(task) = app.selected_task() else { return };
+    let clock = app.store.clock();
+    let terms = app.strategy.explain(task, &app.all_tasks, &app.config.urgency, &clock);
+    let urgency = app.strategy.urgency(task, &app.all_tasks, &app.config.urgency, &clock);
+    let mut rows: Vec<Row> = terms
+        .iter()
+        .map(|t| Row::new(vec![Cell::from(t.name.clone()), Cell::from(format!("{:>+8.2}", t.value))]))
+        .collect();
+    rows.push(
+        Row::new(vec![Cell::from("Urgency"), Cell::from(format!("{:>8.2}", urgency))])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
+
+    let area = centered_rect(50, (rows.len() as u16 + 2).min(f.area().height), f.area());
+    f.render_widget(Clear, area);
+    let table = Table::new(rows, [Constraint::Min(10), Constraint::Length(8)])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Urgency - Task {} (Esc to close)", task.id)),
+        );
+    f.render_widget(table, area);
+}
+
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1,29 +1,488 @@
-use chrono::Local;
-use crate::models::Task;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::clock::Clock;
+use crate::deps::{blocking, is_blocked};
+use crate::error::{Result, TaskustError};
+use crate::models::{Priority, Status, Task};
+
+/// Urgency added by a [`Priority::High`](crate::models::Priority::High) task unless
+/// `priority` is set in `config.json`.
+///
+/// Large enough that a high-priority task due next week outranks a one-hour chore due tomorrow.
+pub const DEFAULT_PRIORITY_WEIGHT: f64 = 15.0;
+
+/// Hours below which [`WeightedShortestJobFirst`] stops favouring a job for being small, so
+/// tasks with almost nothing left don't swamp the list.
+pub const WSJF_MIN_HOURS: f64 = 0.5;
+
+/// Coefficients of the urgency model, read from the `urgency` section of `config.json`.
+///
+/// Every field is optional; the defaults reproduce the built-in model, and the extra
+/// terms (age, tags and projects) add nothing until given a weight.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct UrgencyConfig {
+    /// Name of the [`UrgencyStrategy`] tasks are sorted by; see [`strategy`].
+    pub strategy: String,
+    /// How the due date and estimate are scored by the standard strategy.
+    pub mode: UrgencyMode,
+    /// Urgency of a task that has just become overdue.
+    pub overdue: f64,
+    /// Urgency added for each day a task is overdue.
+    pub overdue_per_day: f64,
+    /// Urgency of a task due in a day, before its estimate is counted; it falls off with
+    /// the days left.
+    pub due: f64,
+    /// Estimated hours that double the urgency from the due date.
+    pub effort_hours: f64,
+    /// Working hours in a day, for [`available_hours`].
+    pub hours_per_day: f64,
+    /// Working days in a week, counted from Monday; 5 means Monday to Friday.
+    pub days_per_week: u32,
+    /// In [`UrgencyMode::Capacity`], urgency added by a task that is [at risk](is_at_risk).
+    pub at_risk: f64,
+    /// Urgency added by a high-priority task; lower priorities add a fraction of it.
+    pub priority: f64,
+    /// Urgency added to a pending task once its scheduled date has arrived.
+    pub scheduled: f64,
+    /// Urgency added for each day a pending task is past its scheduled date, for up to
+    /// `scheduled_max_days`.
+    pub scheduled_per_day: f64,
+    /// Days after which a task that hasn't started stops gaining urgency from its scheduled date.
+    pub scheduled_max_days: f64,
+    /// Factor, between 0 and 1, applied to the urgency of a task waiting on open dependencies.
+    pub blocked_factor: f64,
+    /// Urgency added to a task for every open task that depends on it.
+    pub blocking: f64,
+    /// Urgency added for each day since the task was created, for up to `age_max_days`.
+    pub age: f64,
+    /// Days after which a task stops gaining urgency from its age.
+    pub age_max_days: f64,
+    /// Urgency added by each tag, e.g. `{"oncall": 10}`.
+    pub tags: BTreeMap<String, f64>,
+    /// Urgency added by a task's project, e.g. `{"Work": 5}`.
+    pub projects: BTreeMap<String, f64>,
+    /// Urgency above which a task is shown in red.
+    pub high: f64,
+    /// Urgency above which a task is shown in yellow.
+    pub medium: f64,
+}
+
+impl Default for UrgencyConfig {
+    fn default() -> UrgencyConfig {
+        UrgencyConfig {
+            strategy: StandardStrategy.name().to_string(),
+            mode: UrgencyMode::Deadline,
+            overdue: 100.0,
+            overdue_per_day: 2.0,
+            due: 10.0,
+            effort_hours: 8.0,
+            hours_per_day: 8.0,
+            days_per_week: 5,
+            at_risk: 20.0,
+            priority: DEFAULT_PRIORITY_WEIGHT,
+            scheduled: 5.0,
+            scheduled_per_day: 1.0,
+            scheduled_max_days: 14.0,
+            blocked_factor: 0.5,
+            blocking: 5.0,
+            age: 0.0,
+            age_max_days: 365.0,
+            tags: BTreeMap::new(),
+            projects: BTreeMap::new(),
+            high: 50.0,
+            medium: 20.0,
+        }
+    }
+}
+
+/// How [`compute_urgency`] scores a task's due date and estimate.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UrgencyMode {
+    /// Urgency grows as the due date nears, and a larger estimate scales it up.
+    #[default]
+    Deadline,
+    /// Urgency grows with the share of the working hours left before the due date that the
+    /// remaining work needs; see [`available_hours`].
+    Capacity,
+}
+
+/// How urgent a task is shown to be, see [`UrgencyConfig::level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrgencyLevel {
+    High,
+    Medium,
+    Low,
+}
+
+impl UrgencyConfig {
+    /// Returns the display level of an urgency score, using the `high` and `medium`
+    /// thresholds.
+    pub fn level(&self, urgency: f64) -> UrgencyLevel {
+        if urgency > self.high {
+            UrgencyLevel::High
+        } else if urgency > self.medium {
+            UrgencyLevel::Medium
+        } else {
+            UrgencyLevel::Low
+        }
+    }
+}
+
+/// A named term of a task's urgency, as listed by [`explain_urgency`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contribution {
+    /// What the term is for, e.g. `Days overdue` or `Tag +oncall`.
+    pub name: String,
+    /// Urgency the term adds; negative if it lowers the score.
+    pub value: f64,
+}
+
+impl Contribution {
+    fn new(name: impl Into<String>, value: f64) -> Contribution {
+        Contribution { name: name.into(), value }
+    }
+}
 
 /// Calculates the urgency score for a given task.
 ///
 /// The score is based on:
-/// - **Due Date**: Closer deadlines yield higher scores. Overdue tasks get a significant boost.
+/// - **Due Date**: Closer deadlines yield higher scores. Overdue tasks get a significant boost,
+///   and on the last day the score rises hour by hour towards it (see [`Clock::hours_left`]).
 /// - **Expected Duration**: Longer tasks slightly increase urgency.
+/// - **Priority**: Adds up to `priority` for high-priority tasks.
+/// - **Scheduled Date**: A pending task that should have started gets `scheduled`, plus
+///   `scheduled_per_day` for every day since.
+/// - **Age, Tags and Project**: Add their configured weights.
+///
+/// In [`UrgencyMode::Capacity`] the estimate is replaced by the remaining work measured against
+/// the [`available_hours`], and tasks [at risk](is_at_risk) get `at_risk` on top.
+///
+/// It is the sum of the terms returned by [`explain_urgency`].
 ///
 /// # Returns
-/// - `-1.0` if the task is completed.
+/// - `-1.0` if the task is done or cancelled.
 /// - A positive float representing urgency (higher is more urgent).
-pub fn compute_urgency(task: &Task) -> f64 {
-    if task.completed {
-        return -1.0;
-    }
-    let today = Local::now().date_naive();
-    let days_left = (task.due_date - today).num_days();
-    let base = if days_left <= 0 {
-        // overdue or due today -> high urgency
-        100.0 + (task.expected_hours) + (days_left.abs() as f64 * 2.0)
-    } else {
+pub fn compute_urgency(task: &Task, config: &UrgencyConfig, clock: &Clock) -> f64 {
+    total(&explain_urgency(task, config, clock))
+}
+
+/// Breaks the urgency of `task` down into named terms; see [`compute_urgency`].
+///
+/// Terms that add nothing are left out.
+pub fn explain_urgency(task: &Task, config: &UrgencyConfig, clock: &Clock) -> Vec<Contribution> {
+    if task.is_closed() {
+        return vec![Contribution::new("Done or cancelled", -1.0)];
+    }
+    let today = clock.today();
+    let days_left = clock.hours_left(task) / 24.0;
+    let mut terms = if days_left <= 0.0 {
+        // overdue -> high urgency, growing every day
+        let effort = match config.mode {
+            UrgencyMode::Deadline => task.expected_hours,
+            UrgencyMode::Capacity => task.remaining_hours(),
+        };
+        vec![
+            Contribution::new("Overdue", config.overdue),
+            Contribution::new("Effort", effort),
+            Contribution::new("Days overdue", days_left.abs() * config.overdue_per_day),
+        ]
+    } else if config.mode == UrgencyMode::Capacity {
+        // the larger the share of the time left the work needs -> higher urgency, up to the
+        // overdue score once it needs all of it
+        let remaining = task.remaining_hours();
+        let load = if remaining > 0.0 { (remaining / available_hours(task, config, clock)).min(1.0) } else { 0.0 };
+        let mut terms = vec![
+            Contribution::new("Due date", config.due / days_left.max(1.0)),
+            Contribution::new("Workload", load * config.overdue),
+        ];
+        if is_at_risk(task, config, clock) {
+            terms.push(Contribution::new("At risk", config.at_risk));
+        }
+        terms
+    } else if days_left < 1.0 {
         // closer due date -> higher urgency; longer tasks increase urgency
-        (1.0 / (days_left as f64)) * 10.0 * (1.0 + task.expected_hours / 8.0)
+        let effort = config.due * task.expected_hours / config.effort_hours;
+        // within a day of the deadline -> climb hour by hour towards the overdue score
+        let progress = 1.0 - days_left;
+        vec![
+            Contribution::new("Due date", config.due + (config.overdue - config.due) * progress),
+            Contribution::new("Effort", effort + (task.expected_hours - effort) * progress),
+        ]
+    } else {
+        let effort = config.due * task.expected_hours / config.effort_hours;
+        vec![
+            Contribution::new("Due date", config.due / days_left),
+            Contribution::new("Effort", effort / days_left),
+        ]
     };
+    terms.push(Contribution::new("Priority", task.priority.coefficient() * config.priority));
+    terms.push(Contribution::new("Scheduled", scheduled_bonus(task, config, today)));
+    terms.push(Contribution::new("Age", age_days(task, clock).min(config.age_max_days) * config.age));
+    for tag in &task.tags {
+        if let Some(weight) = config.tags.get(tag) {
+            terms.push(Contribution::new(format!("Tag +{}", tag), *weight));
+        }
+    }
+    if let Some((project, weight)) = task.project.as_ref().and_then(|p| config.projects.get_key_value(p)) {
+        terms.push(Contribution::new(format!("Project {}", project), *weight));
+    }
+    terms.retain(|t| t.value != 0.0);
+    terms
+}
+
+/// Adds up the terms of an urgency breakdown.
+fn total(terms: &[Contribution]) -> f64 {
+    let urgency: f64 = terms.iter().map(|t| t.value).sum();
     // clamp to a reasonable range
-    if base.is_finite() { base } else { 0.0 }
+    if urgency.is_finite() { urgency } else { 0.0 }
+}
+
+/// Returns the working hours left before `task` is due: [`UrgencyConfig::hours_per_day`] for
+/// every working day from today to the due date, but no more than the hours actually left.
+pub fn available_hours(task: &Task, config: &UrgencyConfig, clock: &Clock) -> f64 {
+    let hours_left = clock.hours_left(task);
+    if hours_left <= 0.0 {
+        return 0.0;
+    }
+    let days = working_days(clock.today(), task.due_date, config.days_per_week);
+    (days as f64 * config.hours_per_day).min(hours_left)
+}
+
+/// Returns the hours to spare if the rest of `task` is worked on in the hours available
+/// before it is due; negative if they are not enough.
+pub fn slack(task: &Task, config: &UrgencyConfig, clock: &Clock) -> f64 {
+    available_hours(task, config, clock) - task.remaining_hours()
+}
+
+/// Returns whether an open task has more work left than there are working hours before it
+/// is due. Overdue tasks are past that point and aren't counted.
+pub fn is_at_risk(task: &Task, config: &UrgencyConfig, clock: &Clock) -> bool {
+    !task.is_closed() && clock.hours_left(task) > 0.0 && slack(task, config, clock) < 0.0
+}
+
+/// Counts the working days from `from` to `to`, both included, where the first
+/// `days_per_week` days of each week (from Monday) are working days.
+fn working_days(from: NaiveDate, to: NaiveDate, days_per_week: u32) -> i64 {
+    if to < from {
+        return 0;
+    }
+    let total = (to - from).num_days() + 1;
+    let first = from.weekday().num_days_from_monday() as i64;
+    let partial = (0..total % 7).filter(|i| (first + i) % 7 < days_per_week as i64).count() as i64;
+    total / 7 * days_per_week.min(7) as i64 + partial
+}
+
+/// Returns the days since `task` was created, or 0 if its timestamp is malformed.
+fn age_days(task: &Task, clock: &Clock) -> f64 {
+    DateTime::parse_from_rfc3339(&task.created_at)
+        .map(|created| (clock.now_utc() - created.with_timezone(&Utc)).num_hours().max(0) as f64 / 24.0)
+        .unwrap_or(0.0)
+}
+
+/// Returns the urgency added because `task` should have started by `today`.
+///
+/// Active tasks have started, and waiting ones can't, so only pending tasks get it.
+fn scheduled_bonus(task: &Task, config: &UrgencyConfig, today: NaiveDate) -> f64 {
+    match task.scheduled {
+        Some(date) if date <= today && task.status_on(today) == Status::Pending => {
+            let days_late = ((today - date).num_days() as f64).min(config.scheduled_max_days);
+            config.scheduled + days_late * config.scheduled_per_day
+        }
+        _ => 0.0,
+    }
+}
+
+/// Calculates the urgency of `task`, taking its dependencies among `tasks` into account.
+///
+/// Starts from [`compute_urgency`], scales it by `blocked_factor` if the task is blocked,
+/// and adds `blocking` for every open task waiting on it.
+pub fn compute_urgency_in(task: &Task, tasks: &[Task], config: &UrgencyConfig, clock: &Clock) -> f64 {
+    total(&explain_urgency_in(task, tasks, config, clock))
 }
 
+/// Breaks the urgency of `task` among `tasks` down into named terms; see [`compute_urgency_in`].
+///
+/// The reduction for a blocked task is listed as a negative `Blocked` term.
+pub fn explain_urgency_in(task: &Task, tasks: &[Task], config: &UrgencyConfig, clock: &Clock) -> Vec<Contribution> {
+    let mut terms = explain_urgency(task, config, clock);
+    if task.is_closed() {
+        return terms;
+    }
+    if is_blocked(task, tasks) {
+        let urgency = total(&terms);
+        terms.push(Contribution::new("Blocked", -urgency * (1.0 - config.blocked_factor)));
+    }
+    let waiting = blocking(task, tasks).len();
+    if waiting > 0 {
+        let noun = if waiting == 1 { "task" } else { "tasks" };
+        terms.push(Contribution::new(format!("Blocking {} {}", waiting, noun), waiting as f64 * config.blocking));
+    }
+    terms
+}
+
+/// A way of ranking tasks: tasks are listed from the highest urgency to the lowest.
+///
+/// The built-in strategies are [`StandardStrategy`], [`EarliestDeadlineFirst`],
+/// [`WeightedShortestJobFirst`] and [`PriorityFirst`]; others can be added with
+/// [`register_strategy`].
+pub trait UrgencyStrategy: Send + Sync {
+    /// Name used to select the strategy in `config.json` and with `list --sort-by`.
+    fn name(&self) -> &str;
+
+    /// Breaks the urgency of `task` among `tasks` down into named terms, as shown by
+    /// `taskust explain`.
+    fn explain(&self, task: &Task, tasks: &[Task], config: &UrgencyConfig, clock: &Clock) -> Vec<Contribution>;
+
+    /// Calculates the urgency of `task` among `tasks`; the sum of [`explain`](Self::explain).
+    fn urgency(&self, task: &Task, tasks: &[Task], config: &UrgencyConfig, clock: &Clock) -> f64 {
+        total(&self.explain(task, tasks, config, clock))
+    }
+
+    /// Returns the colour level of a score; by default it is compared with the `high` and
+    /// `medium` thresholds.
+    fn level(&self, urgency: f64, config: &UrgencyConfig) -> UrgencyLevel {
+        config.level(urgency)
+    }
+}
+
+/// The default model of [`compute_urgency_in`]: due date, estimate, priority, dependencies
+/// and the configured extras.
+pub struct StandardStrategy;
+
+impl UrgencyStrategy for StandardStrategy {
+    fn name(&self) -> &str {
+        "standard"
+    }
+
+    fn explain(&self, task: &Task, tasks: &[Task], config: &UrgencyConfig, clock: &Clock) -> Vec<Contribution> {
+        explain_urgency_in(task, tasks, config, clock)
+    }
+}
+
+/// Ranks tasks by their deadline alone: the score is minus the days left, so overdue tasks
+/// score the days they are overdue.
+pub struct EarliestDeadlineFirst;
+
+impl UrgencyStrategy for EarliestDeadlineFirst {
+    fn name(&self) -> &str {
+        "edf"
+    }
+
+    fn explain(&self, task: &Task, _tasks: &[Task], _config: &UrgencyConfig, clock: &Clock) -> Vec<Contribution> {
+        if task.is_closed() {
+            return vec![Contribution::new("Done or cancelled", -1.0)];
+        }
+        match clock.hours_left(task) / 24.0 {
+            days if days < 0.0 => vec![Contribution::new("Days overdue", -days)],
+            days => vec![Contribution::new("Days left", -days)],
+        }
+    }
+
+    /// Tasks due within a day are high and within a week medium.
+    fn level(&self, urgency: f64, _config: &UrgencyConfig) -> UrgencyLevel {
+        if urgency > -1.0 {
+            UrgencyLevel::High
+        } else if urgency > -7.0 {
+            UrgencyLevel::Medium
+        } else {
+            UrgencyLevel::Low
+        }
+    }
+}
+
+/// Weighted shortest job first: the cost of delay, 1 plus the priority's urgency, divided
+/// by the hours left to do, so small valuable tasks come first.
+pub struct WeightedShortestJobFirst;
+
+impl UrgencyStrategy for WeightedShortestJobFirst {
+    fn name(&self) -> &str {
+        "wsjf"
+    }
+
+    fn explain(&self, task: &Task, _tasks: &[Task], config: &UrgencyConfig, _clock: &Clock) -> Vec<Contribution> {
+        if task.is_closed() {
+            return vec![Contribution::new("Done or cancelled", -1.0)];
+        }
+        let cost = 1.0 + task.priority.coefficient() * config.priority;
+        let size = task.remaining_hours().max(WSJF_MIN_HOURS);
+        vec![Contribution::new(format!("Cost of delay {:.2} / {:.1}h", cost, size), cost / size)]
+    }
+}
+
+/// Ranks tasks by priority alone, keeping the order of tasks with the same priority.
+pub struct PriorityFirst;
+
+impl UrgencyStrategy for PriorityFirst {
+    fn name(&self) -> &str {
+        "priority"
+    }
+
+    fn explain(&self, task: &Task, _tasks: &[Task], config: &UrgencyConfig, _clock: &Clock) -> Vec<Contribution> {
+        if task.is_closed() {
+            return vec![Contribution::new("Done or cancelled", -1.0)];
+        }
+        vec![Contribution::new("Priority", task.priority.coefficient() * config.priority)]
+    }
+
+    /// High priority is high and medium priority medium.
+    fn level(&self, urgency: f64, config: &UrgencyConfig) -> UrgencyLevel {
+        if urgency >= Priority::High.coefficient() * config.priority {
+            UrgencyLevel::High
+        } else if urgency >= Priority::Medium.coefficient() * config.priority {
+            UrgencyLevel::Medium
+        } else {
+            UrgencyLevel::Low
+        }
+    }
+}
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn UrgencyStrategy>>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn UrgencyStrategy>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        RwLock::new(vec![
+            Arc::new(StandardStrategy),
+            Arc::new(EarliestDeadlineFirst),
+            Arc::new(WeightedShortestJobFirst),
+            Arc::new(PriorityFirst),
+        ])
+    })
+}
+
+/// Makes `strategy` available by its name, replacing any strategy of the same name.
+///
+/// Register before loading the configuration, which checks the strategy it names exists.
+pub fn register_strategy(strategy: impl UrgencyStrategy + 'static) {
+    let mut strategies = registry().write().unwrap_or_else(|e| e.into_inner());
+    strategies.retain(|s| s.name() != strategy.name());
+    strategies.push(Arc::new(strategy));
+}
+
+/// Returns the names of the registered strategies, built-in ones first.
+pub fn strategy_names() -> Vec<String> {
+    let strategies = registry().read().unwrap_or_else(|e| e.into_inner());
+    strategies.iter().map(|s| s.name().to_string()).collect()
+}
+
+/// Returns the registered strategy called `name`.
+pub fn strategy(name: &str) -> Result<Arc<dyn UrgencyStrategy>> {
+    let strategies = registry().read().unwrap_or_else(|e| e.into_inner());
+    strategies.iter().find(|s| s.name() == name).cloned().ok_or_else(|| {
+        let names: Vec<&str> = strategies.iter().map(|s| s.name()).collect();
+        TaskustError::InvalidArgument(format!("Unknown urgency strategy '{}'. Use one of: {}.", name, names.join(", ")))
+    })
+}
+
+/// Sorts `tasks` from the most urgent to the least by `strategy`, with done and cancelled
+/// tasks last. `all` is every task, for dependencies.
+pub fn sort_by_urgency(tasks: &mut [Task], all: &[Task], strategy: &dyn UrgencyStrategy, config: &UrgencyConfig, clock: &Clock) {
+    let key = |t: &Task| (t.is_closed(), strategy.urgency(t, all, config, clock));
+    tasks.sort_by(|a, b| {
+        let ((a_closed, a), (b_closed, b)) = (key(a), key(b));
+        a_closed.cmp(&b_closed).then(b.partial_cmp(&a).unwrap_or(Ordering::Equal))
+    });
+}
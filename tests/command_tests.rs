@@ -1,64 +1,43 @@
+use taskust::clock::Clock;
 use taskust::commands::*;
-use taskust::storage::{load_tasks, load_templates};
+use taskust::config::Config;
+use taskust::deps;
+use taskust::error::TaskustError;
+use taskust::models::{Priority, Status, Task, Template};
+use taskust::storage::{Backend, JsonStorage, TaskStore};
+use taskust::urgency::{self, sort_by_urgency, UrgencyMode};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
-
-// Use a mutex to ensure tests run serially since they modify the environment variable
-static TEST_MUTEX: Mutex<()> = Mutex::new(());
+use std::time::Duration;
 
 fn with_test_db<F>(test_name: &str, f: F)
 where
-    F: FnOnce(PathBuf),
+    F: FnOnce(&TaskStore),
 {
-    let _guard = TEST_MUTEX.lock().unwrap();
-    
-    let mut db_path = env::temp_dir();
-    db_path.push(format!("taskust_test_{}.json", test_name));
-    
-    // Set env var
-    env::set_var("TASKS_DB", db_path.to_str().unwrap());
-    
+    let mut dir = env::temp_dir();
+    dir.push(format!("taskust_test_{}_{}", test_name, std::process::id()));
+
     // Clean up before test
-    if db_path.exists() {
-        fs::remove_file(&db_path).unwrap();
-    }
-    let mut archive_path = db_path.clone();
-    archive_path.pop();
-    archive_path.push("archive.json");
-    if archive_path.exists() {
-        fs::remove_file(&archive_path).unwrap();
-    }
-    let mut templates_path = db_path.clone();
-    templates_path.pop();
-    templates_path.push("templates.json");
-    if templates_path.exists() {
-        fs::remove_file(&templates_path).unwrap();
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     // Run test
-    f(db_path.clone());
+    let store = TaskStore::open(&dir).unwrap();
+    f(&store);
 
     // Clean up after test
-    if db_path.exists() {
-        fs::remove_file(&db_path).unwrap();
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
     }
-    if archive_path.exists() {
-        fs::remove_file(&archive_path).unwrap();
-    }
-    if templates_path.exists() {
-        fs::remove_file(&templates_path).unwrap();
-    }
-    env::remove_var("TASKS_DB");
 }
 
 #[test]
 fn test_add_and_list() {
-    with_test_db("add_list", |_path| {
-        cmd_add("Test Task".into(), Some("Project".into()), Some(1.0), "2025-12-01".into(), None, None, true);
+    with_test_db("add_list", |store| {
+        cmd_add(store, NewTask { name: "Test Task".into(), project: Some("Project".into()), hours: Some(1.0), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
         
-        let tasks = load_tasks();
+        let tasks = store.load_tasks().unwrap();
         assert_eq!(tasks.len(), 1);
         assert_eq!(tasks[0].name, "Test Task");
         assert_eq!(tasks[0].project, Some("Project".into()));
@@ -67,35 +46,35 @@ fn test_add_and_list() {
 
 #[test]
 fn test_complete_task() {
-    with_test_db("complete", |_path| {
-        cmd_add("Task to complete".into(), None, None, "2025-12-01".into(), None, None, true);
-        let tasks = load_tasks();
+    with_test_db("complete", |store| {
+        cmd_add(store, NewTask { name: "Task to complete".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
         let id = tasks[0].id;
 
-        cmd_complete(id, true);
+        cmd_complete(store, id, false, true).unwrap();
         
-        let tasks = load_tasks();
-        assert!(tasks[0].completed);
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[0].status, Status::Done);
         assert!(tasks[0].completed_at.is_some());
     });
 }
 
 #[test]
 fn test_archive_task() {
-    with_test_db("archive", |_path| {
-        cmd_add("Task to archive".into(), None, None, "2025-12-01".into(), None, None, true);
-        let tasks = load_tasks();
+    with_test_db("archive", |store| {
+        cmd_add(store, NewTask { name: "Task to archive".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
         let id = tasks[0].id;
 
-        cmd_complete(id, true);
+        cmd_complete(store, id, false, true).unwrap();
         
         // Archive all completed tasks
-        cmd_archive(None, true);
+        cmd_archive(store, None, true).unwrap();
 
-        let tasks = load_tasks();
+        let tasks = store.load_tasks().unwrap();
         assert!(tasks.is_empty());
 
-        let archived = taskust::storage::load_archived_tasks();
+        let archived = store.load_archived_tasks().unwrap();
         assert_eq!(archived.len(), 1);
         assert_eq!(archived[0].id, id);
     });
@@ -103,19 +82,19 @@ fn test_archive_task() {
 
 #[test]
 fn test_recurrence() {
-    with_test_db("recurrence", |_path| {
-        cmd_add("Recurring Task".into(), None, None, "2025-12-01".into(), None, Some("daily".into()), true);
-        let tasks = load_tasks();
+    with_test_db("recurrence", |store| {
+        cmd_add(store, NewTask { name: "Recurring Task".into(), due: "2025-12-01".into(), recur: Some("daily".into()), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
         let id = tasks[0].id;
 
-        cmd_complete(id, true);
+        cmd_complete(store, id, false, true).unwrap();
 
-        let tasks = load_tasks();
+        let tasks = store.load_tasks().unwrap();
         // Should have 2 tasks: one completed, one new
         assert_eq!(tasks.len(), 2);
         
-        let completed = tasks.iter().find(|t| t.completed).unwrap();
-        let new_task = tasks.iter().find(|t| !t.completed).unwrap();
+        let completed = tasks.iter().find(|t| t.is_closed()).unwrap();
+        let new_task = tasks.iter().find(|t| !t.is_closed()).unwrap();
         
         assert_eq!(completed.name, "Recurring Task");
         assert_eq!(new_task.name, "Recurring Task");
@@ -125,19 +104,19 @@ fn test_recurrence() {
 
 #[test]
 fn test_template_creation_and_usage() {
-    with_test_db("template_usage", |_path| {
+    with_test_db("template_usage", |store| {
         // Create a template
-        cmd_template_add("dev".into(), Some("Coding".into()), 2.0, true);
+        cmd_template_add(store, Template { name: "dev".into(), project: Some("Coding".into()), default_hours: 2.0, ..Default::default() }, true).unwrap();
         
-        let templates = load_templates();
+        let templates = store.load_templates().unwrap();
         assert_eq!(templates.len(), 1);
         assert_eq!(templates[0].name, "dev");
         assert_eq!(templates[0].default_hours, 2.0);
 
         // Create task using template
-        cmd_add("Task 1".into(), None, None, "2025-12-01".into(), Some("dev".into()), None, true);
+        cmd_add(store, NewTask { name: "Task 1".into(), due: "2025-12-01".into(), template: Some("dev".into()), ..Default::default() }, true).unwrap();
         
-        let tasks = load_tasks();
+        let tasks = store.load_tasks().unwrap();
         assert_eq!(tasks.len(), 1);
         assert_eq!(tasks[0].project, Some("Coding".into()));
         assert_eq!(tasks[0].expected_hours, 2.0);
@@ -147,22 +126,22 @@ fn test_template_creation_and_usage() {
 
 #[test]
 fn test_template_auto_update() {
-    with_test_db("template_update", |_path| {
-        cmd_template_add("writing".into(), Some("Docs".into()), 1.0, true);
+    with_test_db("template_update", |store| {
+        cmd_template_add(store, Template { name: "writing".into(), project: Some("Docs".into()), default_hours: 1.0, ..Default::default() }, true).unwrap();
         
         // Add task with template
-        cmd_add("Doc 1".into(), None, None, "2025-12-01".into(), Some("writing".into()), None, true);
-        let tasks = load_tasks();
+        cmd_add(store, NewTask { name: "Doc 1".into(), due: "2025-12-01".into(), template: Some("writing".into()), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
         let id = tasks[0].id;
 
         // Log more hours than expected (3.0 total)
-        cmd_log(id, 3.0, true);
+        cmd_log(store, id, 3.0, None, None, true).unwrap();
         
         // Complete task
-        cmd_complete(id, true);
+        cmd_complete(store, id, false, true).unwrap();
 
         // Check template updated
-        let templates = load_templates();
+        let templates = store.load_templates().unwrap();
         assert_eq!(templates[0].name, "writing");
         assert_eq!(templates[0].default_hours, 3.0);
     });
@@ -170,16 +149,936 @@ fn test_template_auto_update() {
 
 #[test]
 fn test_template_remove() {
-    with_test_db("template_remove", |_path| {
-        cmd_template_add("temp".into(), None, 1.0, true);
-        cmd_add("Task".into(), None, None, "2025-12-01".into(), Some("temp".into()), None, true);
+    with_test_db("template_remove", |store| {
+        cmd_template_add(store, Template { name: "temp".into(), default_hours: 1.0, ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Task".into(), due: "2025-12-01".into(), template: Some("temp".into()), ..Default::default() }, true).unwrap();
         
-        cmd_template_remove("temp".into(), true);
+        cmd_template_remove(store, "temp".into(), true).unwrap();
         
-        let templates = load_templates();
+        let templates = store.load_templates().unwrap();
         assert!(templates.is_empty());
 
-        let tasks = load_tasks();
+        let tasks = store.load_tasks().unwrap();
         assert_eq!(tasks[0].template, None);
     });
 }
+
+#[test]
+fn test_independent_stores() {
+    with_test_db("store_a", |a| {
+        with_test_db("store_b", |b| {
+            cmd_add(a, NewTask { name: "Task A".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+            cmd_add(b, NewTask { name: "Task B1".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+            cmd_add(b, NewTask { name: "Task B2".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+
+            assert_eq!(a.load_tasks().unwrap().len(), 1);
+            assert_eq!(b.load_tasks().unwrap().len(), 2);
+            assert_eq!(a.load_tasks().unwrap()[0].name, "Task A");
+        });
+    });
+}
+
+#[test]
+fn test_open_file_path() {
+    let mut dir = env::temp_dir();
+    dir.push(format!("taskust_test_file_path_{}", std::process::id()));
+    let store = TaskStore::open(dir.join("custom.json")).unwrap();
+    assert_eq!(store.dir(), dir);
+    assert_eq!(store.backend(), Backend::Json);
+
+    let json = JsonStorage::open(dir.join("custom.json"));
+    assert_eq!(json.tasks_path(), dir.join("custom.json"));
+    assert_eq!(json.templates_path(), dir.join("templates.json"));
+    assert_eq!(json.archive_path(), dir.join("archive.json"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_missing_task_is_an_error() {
+    with_test_db("missing_task", |store| {
+        assert!(matches!(cmd_complete(store, 42, false, true), Err(TaskustError::NotFound(42))));
+        assert!(matches!(cmd_remove(store, 42, true), Err(TaskustError::NotFound(42))));
+        assert!(matches!(
+            cmd_add(store, NewTask { name: "Bad".into(), due: "2025-13-40".into(), ..Default::default() }, true),
+            Err(TaskustError::InvalidDate(_))
+        ));
+        assert!(matches!(
+            cmd_add(store, NewTask { name: "Bad".into(), due: "2025-12-01".into(), recur: Some("hourly".into()), ..Default::default() }, true),
+            Err(TaskustError::UnknownRecurrence(_))
+        ));
+    });
+}
+
+#[test]
+fn test_corrupt_database_is_not_overwritten() {
+    with_test_db("corrupt", |store| {
+        let tasks_path = store.dir().join("tasks.json");
+        cmd_add(store, NewTask { name: "Keep me".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        let mut contents = fs::read_to_string(&tasks_path).unwrap();
+        contents.push('x');
+        fs::write(&tasks_path, &contents).unwrap();
+
+        assert!(matches!(store.load_tasks(), Err(TaskustError::Parse { .. })));
+        assert!(cmd_add(store, NewTask { name: "New".into(), due: "2025-12-01".into(), ..Default::default() }, true).is_err());
+        assert_eq!(fs::read_to_string(&tasks_path).unwrap(), contents);
+    });
+}
+
+#[test]
+fn test_backups_and_restore() {
+    with_test_db("backups", |store| {
+        cmd_add(store, NewTask { name: "First".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Second".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Third".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+
+        let json = JsonStorage::open(store.dir());
+        assert!(json.backup_path(1).exists());
+        assert!(json.backup_path(2).exists());
+        assert!(!json.tasks_path().with_file_name("tasks.json.tmp").exists());
+
+        cmd_restore(store, 2, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].name, "First");
+
+        // The state before the restore is kept as the newest backup
+        cmd_restore(store, 1, true).unwrap();
+        assert_eq!(store.load_tasks().unwrap().len(), 3);
+
+        assert!(matches!(cmd_restore(store, 9, true), Err(TaskustError::BackupNotFound(_))));
+    });
+}
+
+#[test]
+fn test_backup_rotation_limit() {
+    with_test_db("backup_limit", |store| {
+        let json = JsonStorage::open(store.dir()).with_backups(2);
+        let store = TaskStore::from_storage(store.dir(), json.clone());
+        for i in 0..5 {
+            cmd_add(&store, NewTask { name: format!("Task {}", i), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        }
+        assert!(json.backup_path(2).exists());
+        assert!(!json.backup_path(3).exists());
+        assert_eq!(store.load_tasks().unwrap().len(), 5);
+    });
+}
+
+#[test]
+fn test_lock_blocks_concurrent_writers() {
+    with_test_db("lock", |store| {
+        let store = store.clone().with_lock_timeout(Duration::from_millis(100));
+        let guard = store.lock().unwrap();
+
+        let result = cmd_add(&store, NewTask { name: "Blocked".into(), due: "2025-12-01".into(), ..Default::default() }, true);
+        match result {
+            Err(TaskustError::Locked { pid }) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("expected lock error, got {:?}", other),
+        }
+
+        drop(guard);
+        cmd_add(&store, NewTask { name: "Unblocked".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_tasks().unwrap().len(), 1);
+    });
+}
+
+#[test]
+fn test_migrate_to_same_backend_is_an_error() {
+    with_test_db("migrate_same", |store| {
+        assert!(matches!(cmd_migrate(store, Backend::Json, true), Err(TaskustError::InvalidArgument(_))));
+        assert!("postgres".parse::<Backend>().is_err());
+    });
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_migrate_sqlite_round_trip() {
+    with_test_db("migrate_sqlite", |store| {
+        cmd_add(store, NewTask { name: "Old".into(), project: Some("P".into()), due: "2025-12-01".into(), template: Some("tmpl".into()), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Open".into(), due: "2025-12-02".into(), recur: Some("weekly".into()), ..Default::default() }, true).unwrap();
+        cmd_complete(store, 1, false, true).unwrap();
+        cmd_archive(store, None, true).unwrap();
+
+        cmd_migrate(store, Backend::Sqlite, true).unwrap();
+        assert!(!store.dir().join("tasks.json").exists());
+
+        let sqlite = TaskStore::open(store.dir()).unwrap();
+        assert_eq!(sqlite.backend(), Backend::Sqlite);
+        assert_eq!(sqlite.load_tasks().unwrap().len(), 1);
+        assert_eq!(sqlite.load_templates().unwrap().len(), 1);
+        assert_eq!(sqlite.load_archived_tasks().unwrap()[0].name, "Old");
+
+        cmd_log(&sqlite, 2, 1.5, None, None, true).unwrap();
+        cmd_complete(&sqlite, 2, false, true).unwrap();
+        assert_eq!(sqlite.load_tasks().unwrap().len(), 2);
+
+        cmd_migrate(&sqlite, Backend::Json, true).unwrap();
+        let json = TaskStore::open(store.dir()).unwrap();
+        assert_eq!(json.backend(), Backend::Json);
+        assert_eq!(json.load_task(2).unwrap().hours_worked(), 1.5);
+        assert_eq!(json.load_archived_tasks().unwrap().len(), 1);
+    });
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_migrate_store_opened_from_a_file() {
+    with_test_db("migrate_file", |store| {
+        let path = store.dir().join("custom.json");
+        let json = TaskStore::open(&path).unwrap();
+        cmd_add(&json, NewTask { name: "Kept".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        assert!(path.exists());
+
+        cmd_migrate(&json, Backend::Sqlite, true).unwrap();
+        assert!(!path.exists());
+        let sqlite = TaskStore::open(&path).unwrap();
+        assert_eq!(sqlite.backend(), Backend::Sqlite);
+        assert_eq!(sqlite.load_task(1).unwrap().name, "Kept");
+
+        let path = store.dir().join("custom.db");
+        cmd_migrate(&sqlite, Backend::Json, true).unwrap();
+        let json = TaskStore::open(&path).unwrap();
+        assert_eq!(json.backend(), Backend::Json);
+        assert_eq!(json.load_task(1).unwrap().name, "Kept");
+        cmd_add(&json, NewTask { name: "New".into(), due: "2025-12-02".into(), ..Default::default() }, true).unwrap();
+        assert!(!path.exists());
+        assert_eq!(TaskStore::open(store.dir()).unwrap().load_tasks().unwrap().len(), 2);
+    });
+}
+
+#[test]
+fn test_legacy_files_are_upgraded() {
+    with_test_db("legacy_format", |store| {
+        let tasks_path = store.dir().join("tasks.json");
+        fs::write(&tasks_path, r#"[
+            {"id": 1, "name": "Old", "project": null, "expected_hours": 2.0,
+             "due_date": "2025-12-01", "created_at": "2025-01-01T00:00:00+00:00"}
+        ]"#).unwrap();
+        fs::write(store.dir().join("templates.json"), r#"[{"name": "t", "project": null, "default_hours": 1.0}]"#).unwrap();
+
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].status, Status::Pending);
+        // The UUID given on upgrade is the same on every load, before the file is rewritten
+        assert_eq!(store.load_tasks().unwrap()[0].uuid, tasks[0].uuid);
+        assert_eq!(store.load_templates().unwrap().len(), 1);
+
+        cmd_log(store, 1, 1.0, None, None, true).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&tasks_path).unwrap()).unwrap();
+        assert_eq!(saved["version"], 13);
+        assert_eq!(saved["tasks"][0]["work_log"][0]["hours"], 1.0);
+    });
+}
+
+#[test]
+fn test_dependency_ids_upgrade_to_uuids() {
+    with_test_db("deps_format", |store| {
+        fs::write(store.dir().join("tasks.json"), r#"{"version": 12, "tasks": [
+            {"id": 1, "uuid": "9b2c1d5e-0f6a-4b7c-8d9e-0a1b2c3d4e5f", "name": "Design", "project": null,
+             "expected_hours": 1.0, "due_date": "2025-12-01", "created_at": "2025-01-01T00:00:00+00:00"},
+            {"id": 2, "uuid": "3e4f5a6b-7c8d-4e9f-a0b1-c2d3e4f5a6b7", "name": "Build", "project": null,
+             "expected_hours": 1.0, "due_date": "2025-12-01", "created_at": "2025-01-01T00:00:00+00:00",
+             "depends_on": [1, 7]}
+        ]}"#).unwrap();
+
+        // IDs become the UUID of the task that has them; unknown IDs are dropped
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[1].depends_on, vec![tasks[0].uuid]);
+        assert_eq!(deps::dependency_ids(&tasks[1], &tasks), vec![1]);
+    });
+}
+
+#[test]
+fn test_newer_format_is_refused() {
+    with_test_db("newer_format", |store| {
+        let tasks_path = store.dir().join("tasks.json");
+        let contents = r#"{"version": 99, "tasks": []}"#;
+        fs::write(&tasks_path, contents).unwrap();
+
+        let err = store.load_tasks().unwrap_err();
+        assert!(matches!(err, TaskustError::UnsupportedVersion { version: 99, .. }));
+        assert_eq!(err.exit_code(), 13);
+        assert!(cmd_add(store, NewTask { name: "New".into(), due: "2025-12-01".into(), ..Default::default() }, true).is_err());
+        assert_eq!(fs::read_to_string(&tasks_path).unwrap(), contents);
+    });
+}
+
+#[test]
+fn test_undo_redo_remove_and_edit() {
+    with_test_db("undo_redo", |store| {
+        cmd_add(store, NewTask { name: "Keep".into(), due: "2025-12-01".into(), template: Some("tmpl".into()), ..Default::default() }, true).unwrap();
+        cmd_edit(store, 1, TaskEdit { name: Some("Renamed".into()), ..Default::default() }, true).unwrap();
+        cmd_remove(store, 1, true).unwrap();
+        assert!(store.load_tasks().unwrap().is_empty());
+
+        cmd_undo(store, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().name, "Renamed");
+        cmd_undo(store, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().name, "Keep");
+
+        // Undoing the add also removes the template it created
+        cmd_undo(store, true).unwrap();
+        assert!(store.load_tasks().unwrap().is_empty());
+        assert!(store.load_templates().unwrap().is_empty());
+        assert!(matches!(cmd_undo(store, true), Err(TaskustError::InvalidArgument(_))));
+
+        cmd_redo(store, true).unwrap();
+        cmd_redo(store, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().name, "Renamed");
+        assert_eq!(store.load_templates().unwrap().len(), 1);
+
+        // A new change discards the redo stack
+        cmd_log(store, 1, 2.0, None, None, true).unwrap();
+        assert!(matches!(cmd_redo(store, true), Err(TaskustError::InvalidArgument(_))));
+    });
+}
+
+#[test]
+fn test_undo_archive() {
+    with_test_db("undo_archive", |store| {
+        cmd_add(store, NewTask { name: "Done".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Open".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_complete(store, 1, false, true).unwrap();
+        cmd_archive(store, None, true).unwrap();
+        assert_eq!(store.load_archived_tasks().unwrap().len(), 1);
+
+        cmd_undo(store, true).unwrap();
+        assert!(store.load_archived_tasks().unwrap().is_empty());
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].status, Status::Done);
+
+        cmd_redo(store, true).unwrap();
+        assert_eq!(store.load_archived_tasks().unwrap().len(), 1);
+        assert_eq!(store.load_tasks().unwrap().len(), 1);
+
+        cmd_reset(store, true).unwrap();
+        assert!(cmd_undo(store, true).is_err());
+    });
+}
+
+#[test]
+fn test_task_history() {
+    with_test_db("history", |store| {
+        cmd_add(store, NewTask { name: "Write report".into(), hours: Some(2.0), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Other".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_edit(store, 1, TaskEdit { due: Some("2025-12-05".into()), ..Default::default() }, true).unwrap();
+        cmd_log(store, 1, 1.5, None, None, true).unwrap();
+        cmd_estimate(store, 1, 3.0, true).unwrap();
+
+        let history = store.load_history(1, None).unwrap();
+        let fields: Vec<&str> = history.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["created", "due_date", "hours_worked", "expected_hours"]);
+        assert_eq!(history[1].old, "2025-12-01");
+        assert_eq!(history[1].new, "2025-12-05");
+        assert_eq!(history[1].operation, "Edit task 1");
+        assert_eq!(history[3].new, 4.5);
+
+        cmd_complete(store, 1, false, true).unwrap();
+        cmd_archive(store, None, true).unwrap();
+        cmd_undo(store, true).unwrap();
+        let history = store.load_history(1, None).unwrap();
+        let last: Vec<(&str, &str)> = history.iter().rev().take(2).map(|e| (e.field.as_str(), e.operation.as_str())).collect();
+        assert_eq!(last, [("created", "Undo: Archive completed tasks"), ("archived", "Archive completed tasks")]);
+
+        // Other tasks have their own history
+        assert_eq!(store.load_history(2, None).unwrap().len(), 1);
+        assert!(store.load_history(99, None).unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_work_log() {
+    with_test_db("work_log", |store| {
+        let store = &store.clone().with_clock(Clock::fixed("2025-11-24T10:00:00".parse().unwrap()));
+        cmd_add(store, NewTask { name: "Task".into(), hours: Some(5.0), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_log(store, 1, 1.5, Some("2025-11-20".into()), Some("Research".into()), true).unwrap();
+        cmd_log(store, 1, 2.0, None, None, true).unwrap();
+        assert!(matches!(cmd_log(store, 1, 1.0, Some("yesterday".into()), None, true), Err(TaskustError::InvalidDate(_))));
+        assert!(matches!(cmd_log(store, 1, -5.0, None, None, true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(cmd_log(store, 1, 0.0, None, None, true), Err(TaskustError::InvalidArgument(_))));
+
+        let task = store.load_task(1).unwrap();
+        assert_eq!(task.work_log.len(), 2);
+        assert_eq!(task.work_log[0].date.to_string(), "2025-11-20");
+        assert_eq!(task.work_log[0].note.as_deref(), Some("Research"));
+        assert_eq!(task.work_log[1].date.to_string(), "2025-11-24");
+        assert_eq!(task.hours_worked(), 3.5);
+
+        cmd_estimate(store, 1, 1.0, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().expected_hours, 4.5);
+
+        // Estimates are never negative, whether added or edited
+        cmd_edit(store, 1, TaskEdit { hours: Some(-3.0), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().expected_hours, 0.0);
+    });
+}
+
+#[test]
+fn test_hours_worked_migrates_to_work_log() {
+    with_test_db("work_log_migration", |store| {
+        fs::write(store.dir().join("tasks.json"), r#"{"version": 1, "tasks": [
+            {"id": 1, "name": "Open", "project": null, "expected_hours": 4.0, "due_date": "2025-12-01",
+             "created_at": "2025-01-02T09:00:00+00:00", "completed": false, "hours_worked": 3.0,
+             "template": null, "recurrence": null, "completed_at": null},
+            {"id": 2, "name": "Done", "project": null, "expected_hours": 1.0, "due_date": "2025-12-01",
+             "created_at": "2025-01-02T09:00:00+00:00", "completed": true, "hours_worked": 1.5,
+             "template": null, "recurrence": null, "completed_at": "2025-02-03T17:00:00+00:00"},
+            {"id": 3, "name": "Untouched", "project": null, "expected_hours": 1.0, "due_date": "2025-12-01",
+             "created_at": "2025-01-02T09:00:00+00:00", "completed": false, "hours_worked": 0.0,
+             "template": null, "recurrence": null, "completed_at": null}
+        ]}"#).unwrap();
+
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[0].hours_worked(), 3.0);
+        assert_eq!(tasks[0].work_log[0].date.to_string(), "2025-01-02");
+        assert_eq!(tasks[1].work_log[0].date.to_string(), "2025-02-03");
+        assert_eq!(tasks[1].status, Status::Done);
+        assert!(tasks[2].work_log.is_empty());
+    });
+}
+
+#[test]
+fn test_timer_start_stop() {
+    with_test_db("timer", |store| {
+        cmd_add(store, NewTask { name: "First".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Second".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        assert!(matches!(cmd_start(store, 9, true), Err(TaskustError::NotFound(9))));
+        assert!(matches!(cmd_stop(store, true), Err(TaskustError::InvalidArgument(_))));
+        let timed = || store.load_timer().unwrap().and_then(|t| t.task(&store.load_tasks().unwrap()).map(|t| t.id));
+
+        // The timer follows the store's clock
+        let at = |time: &str| store.clone().with_clock(Clock::fixed(format!("2025-11-24T{}:00", time).parse().unwrap()));
+        cmd_start(&at("09:00"), 1, true).unwrap();
+        assert!(matches!(cmd_start(&at("09:30"), 1, true), Err(TaskustError::InvalidArgument(_))));
+        assert_eq!(store.load_timer().unwrap().unwrap().elapsed(&at("09:45").clock()).num_minutes(), 45);
+
+        // Starting another task stops the first one
+        cmd_start(&at("10:30"), 2, true).unwrap();
+        let first = store.load_task(1).unwrap();
+        assert_eq!(first.work_log.len(), 1);
+        assert_eq!(first.hours_worked(), 1.5);
+        assert_eq!(first.work_log[0].date.to_string(), "2025-11-24");
+        assert_eq!(timed(), Some(2));
+
+        cmd_stop(&at("11:00"), true).unwrap();
+        assert!(store.load_timer().unwrap().is_none());
+        assert_eq!(store.load_task(2).unwrap().hours_worked(), 0.5);
+        cmd_list(store, false, &TaskFilter::default(), None).unwrap();
+
+        // Undo restores the timer along with the tasks
+        cmd_undo(store, true).unwrap();
+        assert_eq!(timed(), Some(2));
+        assert!(store.load_task(2).unwrap().work_log.is_empty());
+        cmd_undo(store, true).unwrap();
+        assert_eq!(timed(), Some(1));
+        assert_eq!(store.load_task(2).unwrap().status, Status::Pending);
+        cmd_redo(store, true).unwrap();
+        assert_eq!(timed(), Some(2));
+    });
+}
+
+#[test]
+fn test_tags_and_filter() {
+    with_test_db("tags", |store| {
+        cmd_template_add(store, Template { name: "review".into(), default_hours: 1.0, tags: vec!["review".into()], ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "PR".into(), due: "2025-12-01".into(), template: Some("review".into()), tags: vec!["+oncall".into()], ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Plain".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        assert!(matches!(
+            cmd_add(store, NewTask { name: "Bad".into(), due: "2025-12-01".into(), tags: vec!["two words".into()], ..Default::default() }, true),
+            Err(TaskustError::InvalidArgument(_))
+        ));
+
+        let pr = store.load_task(1).unwrap();
+        assert_eq!(pr.tags, vec!["oncall".to_string(), "review".to_string()]);
+
+        cmd_edit(store, 1, TaskEdit { add_tags: vec!["blocked".into()], remove_tags: vec!["+oncall".into()], ..Default::default() }, true).unwrap();
+        let pr = store.load_task(1).unwrap();
+        assert_eq!(pr.tags, vec!["review".to_string(), "blocked".to_string()]);
+
+        let filter = TaskFilter::parse("+review -blocked", &Config::default()).unwrap();
+        assert_eq!(filter, TaskFilter::new(vec!["review".into()], vec!["blocked".into()]).unwrap());
+        assert_eq!(filter.to_string(), "+review -blocked");
+        assert!(!filter.matches(&pr, store.clock().today()));
+        assert!(TaskFilter::parse("+review", &Config::default()).unwrap().matches(&pr, store.clock().today()));
+        assert!(!TaskFilter::parse("review", &Config::default()).unwrap().matches(&store.load_task(2).unwrap(), store.clock().today()));
+        cmd_list(store, false, &filter, None).unwrap();
+    });
+}
+
+#[test]
+fn test_priority() {
+    with_test_db("priority", |store| {
+        cmd_template_add(store, Template { name: "incident".into(), default_hours: 1.0, priority: Priority::High, ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Fix prod".into(), due: "2025-12-01".into(), template: Some("incident".into()), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Chore".into(), due: "2025-12-01".into(), template: Some("incident".into()), priority: Some(Priority::Low), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().priority, Priority::High);
+        assert_eq!(store.load_task(2).unwrap().priority, Priority::Low);
+
+        cmd_edit(store, 2, TaskEdit { priority: Some("none".parse().unwrap()), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(2).unwrap().priority, Priority::None);
+        assert!(matches!("urgent".parse::<Priority>(), Err(TaskustError::InvalidArgument(_))));
+        assert_eq!("H".parse::<Priority>().unwrap(), Priority::High);
+    });
+}
+
+#[test]
+fn test_dependencies() {
+    with_test_db("deps", |store| {
+        for name in ["Design", "Build", "Ship"] {
+            cmd_add(store, NewTask { name: name.into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        }
+        let depend = |id, deps: &str| cmd_edit(store, id, TaskEdit { depends_on: Some(parse_ids(deps).unwrap()), ..Default::default() }, true);
+        let uuid = |id| store.load_task(id).unwrap().uuid;
+        depend(2, "1").unwrap();
+        depend(3, "1, 2").unwrap();
+        assert_eq!(store.load_task(3).unwrap().depends_on, vec![uuid(1), uuid(2)]);
+
+        let err = depend(1, "3").unwrap_err();
+        assert!(matches!(&err, TaskustError::DependencyCycle(ids) if ids.first() == ids.last()));
+        assert_eq!(err.exit_code(), 14);
+        assert!(matches!(depend(1, "1"), Err(TaskustError::DependencyCycle(_))));
+        assert!(matches!(depend(1, "9"), Err(TaskustError::NotFound(9))));
+        assert!(store.load_task(1).unwrap().depends_on.is_empty());
+        assert!(matches!(parse_ids("1,x"), Err(TaskustError::InvalidArgument(_))));
+
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(task_status(&tasks[1], &tasks, store.clock().today()), "Blocked");
+        cmd_complete(store, 1, false, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(task_status(&tasks[1], &tasks, store.clock().today()), "Pending");
+        assert_eq!(task_status(&tasks[2], &tasks, store.clock().today()), "Blocked");
+        cmd_deps(store, 3).unwrap();
+
+        // Removed and archived dependencies are kept but no longer block, and a task that
+        // reuses the short ID of one doesn't take its place
+        let (design, build) = (uuid(1), uuid(2));
+        cmd_remove(store, 2, true).unwrap();
+        assert_eq!(store.load_task(3).unwrap().depends_on, vec![design, build]);
+        cmd_add(store, NewTask { name: "Review".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        depend(3, "4").unwrap();
+        let review = uuid(4);
+        cmd_complete(store, 4, false, true).unwrap();
+        cmd_archive(store, None, true).unwrap();
+        cmd_add(store, NewTask { name: "Newcomer".into(), due: "2025-12-01".into(), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[0].depends_on, vec![review]);
+        assert_eq!(task_status(&tasks[0], &tasks, store.clock().today()), "Pending");
+        assert!(deps::dependency_ids(&tasks[0], &tasks).is_empty());
+        cmd_deps(store, 3).unwrap();
+        depend(3, "").unwrap();
+        assert!(store.load_task(3).unwrap().depends_on.is_empty());
+    });
+}
+
+#[test]
+fn test_checklist() {
+    with_test_db("checklist", |store| {
+        let steps = vec!["Bump version".into(), "Tag release".into()];
+        cmd_template_add(store, Template { name: "release".into(), default_hours: 1.0, checklist: steps, ..Default::default() }, true).unwrap();
+        let new = NewTask {
+            name: "Release".into(),
+            due: "2025-12-01".into(),
+            template: Some("release".into()),
+            recur: Some("weekly".into()),
+            checklist: vec!["Publish crate".into()],
+            ..Default::default()
+        };
+        cmd_add(store, new, true).unwrap();
+        let texts: Vec<_> = store.load_task(1).unwrap().checklist.into_iter().map(|i| i.text).collect();
+        assert_eq!(texts, ["Bump version", "Tag release", "Publish crate"]);
+
+        cmd_item_remove(store, 1, 3, true).unwrap();
+        cmd_item_check(store, 1, 1, true, true).unwrap();
+        assert_eq!(name_with_progress(&store.load_task(1).unwrap()), "Release [1/2]");
+        cmd_item_check(store, 1, 2, true, true).unwrap();
+        cmd_item_check(store, 1, 2, false, true).unwrap();
+        assert!(matches!(cmd_item_check(store, 1, 3, true, true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(cmd_item_check(store, 1, 0, true, true), Err(TaskustError::InvalidArgument(_))));
+        cmd_item_add(store, 1, "Announce".into(), true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().open_items(), 2);
+
+        // Open items block completion unless forced
+        assert!(matches!(cmd_complete(store, 1, false, true), Err(TaskustError::InvalidArgument(_))));
+        assert_eq!(store.load_task(1).unwrap().status, Status::Pending);
+        cmd_complete(store, 1, true, true).unwrap();
+
+        // The next occurrence starts with a fresh checklist
+        let next = store.load_tasks().unwrap().into_iter().find(|t| !t.is_closed()).unwrap();
+        assert_eq!(next.checklist.len(), 3);
+        assert_eq!(next.open_items(), 3);
+    });
+}
+
+#[test]
+fn test_description_and_annotations() {
+    with_test_db("notes", |store| {
+        cmd_add(store, NewTask { name: "Fix login".into(), due: "2025-12-01".into(), recur: Some("weekly".into()), description: Some("See TICKET-42".into()), ..Default::default() }, true).unwrap();
+        cmd_annotate(store, 1, "Reproduced on staging".into(), true).unwrap();
+        cmd_annotate(store, 1, "Waiting on review".into(), true).unwrap();
+        assert!(matches!(cmd_annotate(store, 1, "  ".into(), true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(cmd_annotate(store, 9, "Lost".into(), true), Err(TaskustError::NotFound(9))));
+
+        let task = store.load_task(1).unwrap();
+        let texts: Vec<_> = task.annotations.iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(texts, ["Reproduced on staging", "Waiting on review"]);
+        cmd_show(store, 1).unwrap();
+
+        cmd_edit(store, 1, TaskEdit { description: Some("Line one\nLine two".into()), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().description.as_deref(), Some("Line one\nLine two"));
+
+        // The next occurrence keeps the description but starts a fresh set of notes
+        cmd_complete(store, 1, false, true).unwrap();
+        let next = store.load_tasks().unwrap().into_iter().find(|t| !t.is_closed()).unwrap();
+        assert_eq!(next.description.as_deref(), Some("Line one\nLine two"));
+        assert!(next.annotations.is_empty());
+
+        cmd_edit(store, 1, TaskEdit { description: Some(String::new()), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(1).unwrap().description, None);
+    });
+}
+
+#[test]
+fn test_status_lifecycle() {
+    with_test_db("status", |store| {
+        cmd_template_add(store, Template { name: "writing".into(), default_hours: 1.0, ..Default::default() }, true).unwrap();
+        for name in ["Draft", "Abandoned", "On hold"] {
+            cmd_add(store, NewTask { name: name.into(), due: "2025-12-01".into(), template: Some("writing".into()), ..Default::default() }, true).unwrap();
+        }
+        cmd_log(store, 1, 2.0, None, None, true).unwrap();
+        cmd_log(store, 2, 8.0, None, None, true).unwrap();
+
+        // Cancelled work doesn't count towards the template average
+        cmd_complete(store, 1, false, true).unwrap();
+        cmd_cancel(store, 2, true).unwrap();
+        assert_eq!(store.load_task(2).unwrap().status, Status::Cancelled);
+        assert_eq!(store.load_templates().unwrap()[0].default_hours, 2.0);
+        assert!(matches!(cmd_cancel(store, 1, true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(cmd_complete(store, 2, false, true), Err(TaskustError::InvalidArgument(_))));
+
+        cmd_wait(store, 3, Some("2099-01-01".into()), true).unwrap();
+        let task = store.load_task(3).unwrap();
+        assert_eq!(task.status, Status::Waiting);
+        assert_eq!(task.status_on("2099-01-01".parse().unwrap()), Status::Pending);
+        assert!(matches!(cmd_wait(store, 3, Some("2000-01-01".into()), true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(cmd_wait(store, 1, None, true), Err(TaskustError::InvalidArgument(_))));
+
+        let tasks = store.load_tasks().unwrap();
+        let filter = TaskFilter::parse("status:waiting", &Config::default()).unwrap();
+        assert_eq!(filter.to_string(), "status:waiting");
+        assert!(filter.shows(&tasks[2], false, store.clock().today()) && !filter.shows(&tasks[0], true, store.clock().today()));
+        let default = TaskFilter::default();
+        assert!(tasks.iter().all(|t| !default.shows(t, false, store.clock().today())));
+        assert!(default.shows(&tasks[1], true, store.clock().today()));
+        assert_eq!(task_status(&tasks[1], &tasks, store.clock().today()), "Cancelled");
+        cmd_list(store, false, &filter, None).unwrap();
+
+        // Starting a timer makes a task active; editing the status reopens a closed task
+        cmd_start(store, 3, true).unwrap();
+        let task = store.load_task(3).unwrap();
+        assert_eq!((task.status, task.wait_until), (Status::Active, None));
+        cmd_stop(store, true).unwrap();
+        cmd_edit(store, 2, TaskEdit { status: Some(Status::Pending), ..Default::default() }, true).unwrap();
+        let task = store.load_task(2).unwrap();
+        assert_eq!((task.status, task.completed_at), (Status::Pending, None));
+        let done = TaskEdit { status: Some(Status::Done), ..Default::default() };
+        assert!(matches!(cmd_edit(store, 2, done, true), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!("finished".parse::<Status>(), Err(TaskustError::InvalidArgument(_))));
+    });
+}
+
+#[test]
+fn test_scheduled_and_wait_dates() {
+    with_test_db("scheduled", |store| {
+        let new = NewTask {
+            name: "Renew certificate".into(),
+            due: "2099-03-31".into(),
+            scheduled: Some("2099-03-15".into()),
+            wait: Some("2099-03-01".into()),
+            recur: Some("monthly".into()),
+            ..Default::default()
+        };
+        cmd_add(store, new, true).unwrap();
+        cmd_add(store, NewTask { name: "Visible".into(), due: "2099-03-31".into(), ..Default::default() }, true).unwrap();
+        let bad = NewTask { name: "Past".into(), due: "2099-03-31".into(), wait: Some("2000-01-01".into()), ..Default::default() };
+        assert!(matches!(cmd_add(store, bad, true), Err(TaskustError::InvalidArgument(_))));
+
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[0].status, Status::Waiting);
+        assert_eq!(tasks[0].scheduled.unwrap().to_string(), "2099-03-15");
+        let default = TaskFilter::default();
+        assert!(!default.shows(&tasks[0], false, store.clock().today()) && default.shows(&tasks[1], false, store.clock().today()));
+        assert!(default.shows(&tasks[0], true, store.clock().today()));
+        assert!(!tasks[0].is_hidden_on("2099-03-01".parse().unwrap()));
+
+        // The next occurrence keeps its dates the same distance from the due date
+        cmd_complete(store, 1, false, true).unwrap();
+        let next = store.load_tasks().unwrap().into_iter().find(|t| !t.is_closed() && t.id != 2).unwrap();
+        assert_eq!(next.due_date.to_string(), "2099-04-30");
+        assert_eq!(next.scheduled.unwrap().to_string(), "2099-04-14");
+        assert_eq!(next.wait_until.unwrap().to_string(), "2099-03-31");
+        assert_eq!(next.status, Status::Waiting);
+
+        cmd_edit(store, 2, TaskEdit { scheduled: Some("2099-01-01".into()), ..Default::default() }, true).unwrap();
+        assert!(store.load_task(2).unwrap().scheduled.is_some());
+        cmd_edit(store, 2, TaskEdit { scheduled: Some(String::new()), ..Default::default() }, true).unwrap();
+        assert_eq!(store.load_task(2).unwrap().scheduled, None);
+        cmd_list(store, false, &default, None).unwrap();
+    });
+}
+
+#[test]
+fn test_due_times() {
+    with_test_db("due_times", |store| {
+        cmd_add(store, NewTask { name: "Submit invoice".into(), due: "2099-12-01 14:00".into(), ..Default::default() }, true).unwrap();
+        let task = store.load_task(1).unwrap();
+        assert_eq!(task.due_time.unwrap().to_string(), "14:00:00");
+        assert_eq!(format_due(&task), "2099-12-01 14:00");
+
+        let bad = NewTask { name: "Bad".into(), due: "2099-12-01 25:00".into(), ..Default::default() };
+        assert!(matches!(cmd_add(store, bad, true), Err(TaskustError::InvalidDate(_))));
+
+        // Editing the due date without a time drops the old time
+        cmd_edit(store, 1, TaskEdit { due: Some("2099-12-02".into()), ..Default::default() }, true).unwrap();
+        let task = store.load_task(1).unwrap();
+        assert_eq!(task.due_time, None);
+        assert_eq!(format_due(&task), "2099-12-02");
+
+        // Close to the deadline the time left is counted in hours
+        cmd_edit(store, 1, TaskEdit { due: Some("2099-12-02T14:00".into()), ..Default::default() }, true).unwrap();
+        let task = store.load_task(1).unwrap();
+        let clock = Clock::fixed("2099-12-02T10:30:00".parse().unwrap());
+        assert_eq!(format_time_left(&task, &clock), "3h left");
+        assert_eq!(format_time_left(&task, &Clock::fixed("2099-12-02T13:15:00".parse().unwrap())), "45m left");
+        assert_eq!(format_time_left(&task, &clock.as_of("2099-11-28".parse().unwrap())), "4d");
+        cmd_list(store, false, &TaskFilter::default(), None).unwrap();
+
+        // TASKS_TIMEZONE and TASKS_DUE_TIME are checked when a store is opened
+        let evening = Clock::from_settings(Some("Europe/Berlin"), Some("17:00")).unwrap();
+        assert_eq!(evening.deadline(&Task { due_time: None, ..task.clone() }).to_string(), "2099-12-02 17:00:00");
+        assert_eq!(evening.deadline(&task).to_string(), "2099-12-02 14:00:00");
+        assert!(matches!(Clock::from_settings(Some("Mars/Olympus"), None), Err(TaskustError::InvalidArgument(_))));
+        assert!(matches!(Clock::from_settings(None, Some("5pm")), Err(TaskustError::InvalidArgument(_))));
+    });
+}
+
+#[test]
+fn test_uuids() {
+    with_test_db("uuids", |store| {
+        cmd_add(store, NewTask { name: "First".into(), due: "2099-12-01".into(), ..Default::default() }, true).unwrap();
+        cmd_add(store, NewTask { name: "Second".into(), due: "2099-12-01".into(), ..Default::default() }, true).unwrap();
+        let tasks = store.load_tasks().unwrap();
+        assert_ne!(tasks[0].uuid, tasks[1].uuid);
+        let first = tasks[0].clone();
+
+        // Either the short ID or a UUID prefix finds the task; the prefix runs past the first
+        // hyphen so it can't be all digits, which would be read as a short ID
+        let prefix = &first.uuid.to_string()[..10];
+        assert_eq!(resolve_id(store, "1").unwrap(), 1);
+        assert_eq!(resolve_id(store, prefix).unwrap(), 1);
+        assert_eq!(resolve_id(store, &prefix.to_uppercase()).unwrap(), 1);
+        assert!(matches!(resolve_id(store, "3"), Err(TaskustError::NotFound(3))));
+        let err = resolve_id(store, "zzzz").unwrap_err();
+        assert!(matches!(err, TaskustError::UuidNotFound(_)));
+        assert_eq!(err.exit_code(), 15);
+        assert!(matches!(resolve_id(store, ""), Err(TaskustError::InvalidArgument(_))));
+
+        // The archived task keeps its UUID while its short ID is reused
+        cmd_complete(store, 2, false, true).unwrap();
+        cmd_archive(store, None, true).unwrap();
+        cmd_remove(store, 1, true).unwrap();
+        cmd_add(store, NewTask { name: "Reused".into(), due: "2099-12-01".into(), ..Default::default() }, true).unwrap();
+        let archived = store.load_archived_tasks().unwrap();
+        assert_eq!(archived[0].uuid, tasks[1].uuid);
+        let reused = store.load_task(1).unwrap();
+        assert_ne!(reused.uuid, first.uuid);
+
+        // History follows the UUID, not the short ID
+        let history = store.load_history(1, Some(reused.uuid)).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].new, "Reused");
+        assert_eq!(store.load_history(1, Some(first.uuid)).unwrap().len(), 2);
+        cmd_history(store, &archived[0].uuid.to_string()[..10]).unwrap();
+
+        // A timer left running on a removed task doesn't log to the task that reuses its ID
+        cmd_start(store, 1, true).unwrap();
+        cmd_remove(store, 1, true).unwrap();
+        cmd_add(store, NewTask { name: "Newcomer".into(), due: "2099-12-01".into(), ..Default::default() }, true).unwrap();
+        assert!(store.load_timer().unwrap().unwrap().task(&store.load_tasks().unwrap()).is_none());
+        cmd_stop(store, true).unwrap();
+        assert!(store.load_timer().unwrap().is_none());
+        assert!(store.load_task(1).unwrap().work_log.is_empty());
+    });
+}
+
+#[test]
+fn test_user_defined_attributes() {
+    with_test_db("udas", |store| {
+        fs::write(store.config_path(), r#"{"udas": [
+            {"name": "points", "type": "number", "label": "Pts", "column": true},
+            {"name": "customer", "type": "string"},
+            {"name": "severity", "type": "enum", "values": ["low", "high"], "column": true},
+            {"name": "reported", "type": "date"}
+        ]}"#).unwrap();
+        let set = |pairs: &[(&str, &str)]| pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect::<Vec<_>>();
+
+        let template = Template { name: "bug".into(), default_hours: 1.0, udas: set(&[("severity", "HIGH")]).into_iter().collect(), ..Default::default() };
+        cmd_template_add(store, template, true).unwrap();
+        assert_eq!(store.load_template("bug").unwrap().unwrap().udas["severity"], "high");
+
+        let new = NewTask { name: "Crash".into(), due: "2099-12-01".into(), template: Some("bug".into()), udas: set(&[("points", "3.0"), ("customer", "Acme")]), ..Default::default() };
+        cmd_add(store, new, true).unwrap();
+        cmd_add(store, NewTask { name: "Chore".into(), due: "2099-12-01".into(), ..Default::default() }, true).unwrap();
+        let task = store.load_task(1).unwrap();
+        assert_eq!(task.udas["points"], "3");
+        assert_eq!(task.udas["severity"], "high");
+        assert_eq!(task.udas["customer"], "Acme");
+
+        // Values are checked against the declared type
+        for (name, value) in [("points", "many"), ("severity", "urgent"), ("reported", "yesterday"), ("owner", "me")] {
+            let edit = TaskEdit { udas: set(&[(name, value)]), ..Default::default() };
+            assert!(cmd_edit(store, 1, edit, true).is_err(), "{}={}", name, value);
+        }
+        let edit = TaskEdit { udas: set(&[("reported", "2099-11-30"), ("customer", "")]), ..Default::default() };
+        cmd_edit(store, 1, edit, true).unwrap();
+        let task = store.load_task(1).unwrap();
+        assert_eq!(task.udas["reported"], "2099-11-30");
+        assert!(!task.udas.contains_key("customer"));
+
+        let config = store.load_config().unwrap();
+        let filter = TaskFilter::parse("severity:HIGH points:3.0", &config).unwrap();
+        assert_eq!(filter.to_string(), "severity:HIGH points:3.0");
+        let tasks = store.load_tasks().unwrap();
+        assert!(filter.matches(&tasks[0], store.clock().today()) && !filter.matches(&tasks[1], store.clock().today()));
+        assert!(TaskFilter::parse("severity:", &config).unwrap().matches(&tasks[1], store.clock().today()));
+
+        // Filtering on an attribute that isn't declared is an error rather than matching nothing
+        assert!(matches!(TaskFilter::parse("owner:me", &config), Err(TaskustError::InvalidArgument(_))));
+        let undeclared = TaskFilter { udas: set(&[("owner", "me")]), ..TaskFilter::default() };
+        assert!(matches!(cmd_list(store, false, &undeclared, None), Err(TaskustError::InvalidArgument(_))));
+        cmd_list(store, false, &TaskFilter::default(), None).unwrap();
+        cmd_show(store, 1).unwrap();
+
+        fs::write(store.config_path(), r#"{"udas": [{"name": "kind", "type": "enum"}]}"#).unwrap();
+        assert!(matches!(store.load_config(), Err(TaskustError::Parse { .. })));
+    });
+}
+
+#[test]
+fn test_urgency_config_file() {
+    with_test_db("urgency_config", |store| {
+        // Monday noon, with tasks due at noon so every task has a whole number of days left
+        let monday = Clock::fixed("2026-03-02T12:00:00".parse().unwrap())
+            .with_zone("UTC".parse().unwrap())
+            .with_due_time("12:00:00".parse().unwrap());
+        let store = &store.clone().with_clock(monday);
+        let explain = |id: u64| {
+            let config = store.load_config().unwrap();
+            let tasks = store.load_tasks().unwrap();
+            let task = tasks.iter().find(|t| t.id == id).unwrap();
+            let terms = urgency::strategy(&config.urgency.strategy).unwrap().explain(task, &tasks, &config.urgency, &monday);
+            terms.into_iter().map(|t| (t.name, t.value)).collect::<Vec<_>>()
+        };
+        let order = |sort_by: &str| {
+            let config = store.load_config().unwrap();
+            let mut tasks = store.load_tasks().unwrap();
+            let all = tasks.clone();
+            sort_by_urgency(&mut tasks, &all, urgency::strategy(sort_by).unwrap().as_ref(), &config.urgency, &monday);
+            tasks.iter().map(|t| t.id).collect::<Vec<_>>()
+        };
+
+        fs::write(store.config_path(), r#"{"urgency": {"high": 5, "tags": {"oncall": 10}}}"#).unwrap();
+        let config = store.load_config().unwrap();
+        assert_eq!(config.urgency.high, 5.0);
+        assert_eq!(config.urgency.tags["oncall"], 10.0);
+        // Coefficients that aren't set keep their defaults
+        assert_eq!(config.urgency.overdue, 100.0);
+        assert_eq!(config.urgency.medium, 20.0);
+
+        // Two days left for an hour of work, plus the tag's weight
+        cmd_add(store, NewTask { name: "Rota".into(), due: "2026-03-04".into(), tags: vec!["oncall".into()], ..Default::default() }, true).unwrap();
+        let terms = vec![("Due date".to_string(), 5.0), ("Effort".to_string(), 0.625), ("Tag +oncall".to_string(), 10.0)];
+        assert_eq!(explain(1), terms);
+        cmd_list(store, false, &TaskFilter::default(), None).unwrap();
+        cmd_explain(store, 1).unwrap();
+        assert!(matches!(cmd_explain(store, 9), Err(TaskustError::NotFound(9))));
+
+        // Monday and Tuesday leave 12 working hours for 30 hours of work
+        fs::write(store.config_path(), r#"{"urgency": {"mode": "capacity", "hours_per_day": 6}}"#).unwrap();
+        let config = store.load_config().unwrap();
+        assert_eq!(config.urgency.mode, UrgencyMode::Capacity);
+        cmd_add(store, NewTask { name: "Rewrite".into(), due: "2026-03-03".into(), hours: Some(30.0), ..Default::default() }, true).unwrap();
+        let rewrite = store.load_task(2).unwrap();
+        assert_eq!(urgency::available_hours(&rewrite, &config.urgency, &monday), 12.0);
+        assert!(urgency::is_at_risk(&rewrite, &config.urgency, &monday));
+        assert_eq!(format_time_left_at_risk(&rewrite, &monday, &config.urgency), "1d at risk");
+        let terms = vec![("Due date".to_string(), 10.0), ("Workload".to_string(), 100.0), ("At risk".to_string(), 20.0)];
+        assert_eq!(explain(2), terms);
+        assert!(!urgency::is_at_risk(&store.load_task(1).unwrap(), &config.urgency, &monday));
+        cmd_list(store, false, &TaskFilter::default(), None).unwrap();
+        cmd_show(store, 2).unwrap();
+        cmd_explain(store, 2).unwrap();
+
+        fs::write(store.config_path(), r#"{"urgency": {"effort_hours": 0}}"#).unwrap();
+        assert!(matches!(store.load_config(), Err(TaskustError::Parse { .. })));
+        fs::write(store.config_path(), r#"{"urgency": {"days_per_week": 8}}"#).unwrap();
+        assert!(matches!(store.load_config(), Err(TaskustError::Parse { .. })));
+        fs::write(store.config_path(), r#"{"urgency": {"blocked_factor": 2}}"#).unwrap();
+        assert!(matches!(store.load_config(), Err(TaskustError::Parse { .. })));
+
+        // Tasks can be sorted by another strategy, from config.json or for one listing
+        let quick = NewTask { name: "Quick win".into(), due: "2026-03-20".into(), hours: Some(0.5), priority: Some(Priority::High), ..Default::default() };
+        fs::write(store.config_path(), r#"{"urgency": {"strategy": "edf"}}"#).unwrap();
+        cmd_add(store, quick, true).unwrap();
+        assert_eq!(order("edf"), vec![2, 1, 3]);
+        assert_eq!(order("wsjf"), vec![3, 1, 2]);
+        assert_eq!(order("priority"), vec![3, 1, 2]);
+        assert_eq!(explain(2), vec![("Days left".to_string(), -1.0)]);
+        cmd_list(store, true, &TaskFilter::default(), None).unwrap();
+        cmd_explain(store, 2).unwrap();
+        cmd_list(store, false, &TaskFilter::default(), Some("wsjf")).unwrap();
+        assert!(matches!(cmd_list(store, false, &TaskFilter::default(), Some("fifo")), Err(TaskustError::InvalidArgument(_))));
+        fs::write(store.config_path(), r#"{"urgency": {"strategy": "fifo"}}"#).unwrap();
+        assert!(matches!(store.load_config(), Err(TaskustError::Parse { .. })));
+    });
+}
+
+#[test]
+fn test_clock() {
+    with_test_db("clock", |store| {
+        let monday = Clock::fixed("2026-03-02T09:00:00".parse().unwrap()).with_zone("UTC".parse().unwrap());
+        let store = store.clone().with_clock(monday);
+        cmd_add(&store, NewTask { name: "Standup".into(), due: "2026-03-02".into(), recur: Some("daily".into()), ..Default::default() }, true).unwrap();
+        // The wait date only has to be after the clock's today
+        cmd_add(&store, NewTask { name: "Renew".into(), due: "2026-03-20".into(), wait: Some("2026-03-10".into()), ..Default::default() }, true).unwrap();
+        assert!(store.load_task(1).unwrap().created_at.starts_with("2026-03-02T"));
+        // The undo journal and the change history are stamped by the same clock
+        assert!(store.load_journal().unwrap().undo[0].timestamp.starts_with("2026-03-02T"));
+        assert!(store.load_history(1, None).unwrap()[0].timestamp.starts_with("2026-03-02T"));
+        // Timestamps carry the offset of the clock's zone, not the machine's
+        let berlin = Clock::fixed("2026-07-01T09:00:00".parse().unwrap()).with_zone("Europe/Berlin".parse().unwrap());
+        assert_eq!(berlin.timestamp(), "2026-07-01T09:00:00+02:00");
+        assert_eq!(monday.timestamp(), "2026-03-02T09:00:00+00:00");
+
+        let renew = store.load_task(2).unwrap();
+        assert_eq!(format_time_left(&renew, &monday), "18d");
+        assert_eq!(monday.hours_left(&renew), 18.0 * 24.0 + 14.0 + 59.0 / 60.0);
+        let default = TaskFilter::default();
+        assert!(!default.shows(&renew, false, monday.today()));
+        assert!(default.shows(&renew, false, monday.as_of("2026-03-10".parse().unwrap()).today()));
+
+        // The next occurrence follows the completion date of the clock
+        cmd_complete(&store, 1, false, true).unwrap();
+        let done = store.load_task(1).unwrap();
+        assert!(done.completed_at.unwrap().contains("2026-03-02"));
+        assert_eq!(store.load_task(3).unwrap().due_date.to_string(), "2026-03-03");
+
+        // Archiving by age counts from the clock's now
+        let later = |date: &str| store.clone().with_clock(monday.as_of(date.parse().unwrap()));
+        cmd_archive(&later("2026-03-05"), Some(7), true).unwrap();
+        assert!(store.load_archived_tasks().unwrap().is_empty());
+        cmd_archive(&later("2026-03-12"), Some(7), true).unwrap();
+        assert_eq!(store.load_archived_tasks().unwrap().len(), 1);
+        cmd_list(&later("2026-03-12"), false, &default, None).unwrap();
+    });
+}
@@ -1,53 +1,306 @@
-use taskust::urgency::compute_urgency;
-use taskust::models::Task;
-use chrono::{Local, Duration};
+use taskust::urgency::{available_hours, compute_urgency, compute_urgency_in, explain_urgency, explain_urgency_in, is_at_risk, register_strategy, slack, sort_by_urgency, strategy, strategy_names, Contribution, UrgencyConfig, UrgencyLevel, UrgencyMode, UrgencyStrategy};
+use taskust::error::TaskustError;
+use taskust::models::{Priority, Status, Task, WorkEntry};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use taskust::clock::Clock;
+use uuid::Uuid;
+use std::collections::BTreeMap;
 
-#[test]
-fn test_urgency_calculation() {
-    let now = Local::now();
-    let today = now.date_naive();
-    let due_tomorrow = today + Duration::days(1);
-    
-    let task = Task {
+/// Monday 2 March 2026 at noon UTC. Tasks without a due time are due at noon too, so tasks
+/// due on other days have a whole number of days left.
+fn monday() -> Clock {
+    Clock::fixed("2026-03-02T12:00:00".parse().unwrap())
+        .with_zone("UTC".parse().unwrap())
+        .with_due_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap())
+}
+
+/// Returns pending task 1, created [`monday`], due on `due` with an estimate of `hours`.
+/// Tests override the fields they check with `Task { .., ..task(..) }`.
+fn task(name: &str, due: NaiveDate, hours: f64) -> Task {
+    Task {
         id: 1,
-        name: "Test".into(),
+        uuid: Uuid::new_v4(),
+        name: name.into(),
         project: None,
-        expected_hours: 1.0,
-        due_date: due_tomorrow,
-        created_at: now.to_rfc3339(),
-        completed: false,
-        hours_worked: 0.0,
+        expected_hours: hours,
+        due_date: due,
+        due_time: None,
+        scheduled: None,
+        created_at: monday().timestamp(),
+        status: Status::Pending,
+        wait_until: None,
+        work_log: Vec::new(),
         template: None,
         recurrence: None,
         completed_at: None,
-    };
+        tags: Vec::new(),
+        priority: Priority::None,
+        depends_on: Vec::new(),
+        checklist: Vec::new(),
+        description: None,
+        annotations: Vec::new(),
+        udas: BTreeMap::new(),
+    }
+}
+
+#[test]
+fn test_urgency_calculation() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let task = task("Test", clock.today() + Duration::days(1), 1.0);
 
-    let urgency = compute_urgency(&task);
+    let urgency = compute_urgency(&task, &config, &clock);
     // Urgency should be positive
     assert!(urgency > 0.0);
+    // A day left for an hour of work: 10 for the due date, plus an eighth for the hour
+    assert_eq!(urgency, 11.25);
 }
 
 #[test]
 fn test_urgency_overdue() {
-    let now = Local::now();
-    let today = now.date_naive();
-    let due_yesterday = today - Duration::days(1);
-    
-    let task = Task {
-        id: 1,
-        name: "Test".into(),
-        project: None,
-        expected_hours: 1.0,
-        due_date: due_yesterday,
-        created_at: now.to_rfc3339(),
-        completed: false,
-        hours_worked: 0.0,
-        template: None,
-        recurrence: None,
-        completed_at: None,
-    };
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let task = task("Test", clock.today() - Duration::days(1), 1.0);
 
-    let urgency = compute_urgency(&task);
+    let urgency = compute_urgency(&task, &config, &clock);
     // Should be very high because it's overdue (base 100 + ...)
     assert!(urgency > 100.0);
+    // 100 for being overdue, 1 for the hour of work and 2 for the day
+    assert_eq!(urgency, 103.0);
+}
+
+#[test]
+fn test_urgency_priority() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let today = clock.today();
+
+    let chore = task("Chore", today + Duration::days(1), 1.0);
+    let fix = Task { id: 2, priority: Priority::High, ..task("Production fix", today + Duration::days(7), 1.0) };
+
+    // A critical fix due next week outranks a trivial chore due tomorrow
+    assert!(compute_urgency(&fix, &config, &clock) > compute_urgency(&chore, &config, &clock));
+    let medium = Task { priority: Priority::Medium, ..fix.clone() };
+    assert!(compute_urgency(&fix, &config, &clock) > compute_urgency(&medium, &config, &clock));
+
+    // The weight comes from the config, so each store can set its own
+    let unweighted = UrgencyConfig { priority: 0.0, ..config.clone() };
+    let plain = Task { priority: Priority::None, ..fix.clone() };
+    assert_eq!(compute_urgency(&fix, &unweighted, &clock), compute_urgency(&plain, &config, &clock));
+    let doubled = UrgencyConfig { priority: 30.0, ..config.clone() };
+    assert_eq!(compute_urgency(&fix, &doubled, &clock) - compute_urgency(&fix, &config, &clock), 15.0);
+}
+
+#[test]
+fn test_urgency_dependencies() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let first = task("First", clock.today() + Duration::days(2), 1.0);
+    let second = Task { id: 2, uuid: Uuid::new_v4(), name: "Second".into(), depends_on: vec![first.uuid], ..first.clone() };
+    let tasks = vec![first.clone(), second.clone()];
+
+    // The blocking task rises, the blocked one drops
+    assert!(compute_urgency_in(&first, &tasks, &config, &clock) > compute_urgency(&first, &config, &clock));
+    assert!(compute_urgency_in(&second, &tasks, &config, &clock) < compute_urgency(&second, &config, &clock));
+    assert_eq!(compute_urgency_in(&first, &tasks, &config, &clock), 11.25 / 2.0 + 5.0);
+    assert_eq!(compute_urgency_in(&second, &tasks, &config, &clock), 11.25 / 2.0 * 0.5);
+
+    // Once the dependency is done, nothing is adjusted
+    let done = Task { status: Status::Done, ..first.clone() };
+    let tasks = vec![done, second.clone()];
+    assert_eq!(compute_urgency_in(&second, &tasks, &config, &clock), compute_urgency(&second, &config, &clock));
+
+    // Both adjustments come from the config
+    let tasks = vec![first.clone(), second.clone()];
+    let custom = UrgencyConfig { blocked_factor: 0.0, blocking: 2.0, ..config.clone() };
+    assert_eq!(compute_urgency_in(&first, &tasks, &custom, &clock), 11.25 / 2.0 + 2.0);
+    assert_eq!(compute_urgency_in(&second, &tasks, &custom, &clock), 0.0);
+}
+
+#[test]
+fn test_urgency_scheduled() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let today = clock.today();
+    let task = task("Quarterly review", today + Duration::days(10), 2.0);
+    let future = Task { scheduled: Some(today + Duration::days(3)), ..task.clone() };
+    let today_task = Task { scheduled: Some(today), ..task.clone() };
+    let late = Task { scheduled: Some(today - Duration::days(4)), ..task.clone() };
+    let very_late = Task { scheduled: Some(today - Duration::days(60)), ..task.clone() };
+
+    assert_eq!(compute_urgency(&future, &config, &clock), compute_urgency(&task, &config, &clock));
+    assert!(compute_urgency(&today_task, &config, &clock) > compute_urgency(&task, &config, &clock));
+    assert!(compute_urgency(&late, &config, &clock) > compute_urgency(&today_task, &config, &clock));
+    // The bonus stops growing after a while
+    assert!(compute_urgency(&very_late, &config, &clock) - compute_urgency(&task, &config, &clock) <= 20.0);
+
+    // 3 points, plus half a point for each of the 4 days late, up to 2 days
+    let custom = UrgencyConfig { scheduled: 3.0, scheduled_per_day: 0.5, scheduled_max_days: 2.0, ..config.clone() };
+    assert_eq!(compute_urgency(&late, &custom, &clock) - compute_urgency(&task, &custom, &clock), 4.0);
+
+    // Work that has started doesn't gain anything from its scheduled date
+    let active = Task { status: Status::Active, ..late };
+    assert_eq!(compute_urgency(&active, &config, &clock), compute_urgency(&Task { status: Status::Active, ..task }, &config, &clock));
+}
+
+#[test]
+fn test_urgency_due_time() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let now = clock.now();
+    let due_in = |hours: i64| {
+        let due = now + Duration::hours(hours);
+        Task { due_time: Some(due.time()), ..task("Deploy", due.date(), 1.0) }
+    };
+
+    // On the last day urgency climbs hour by hour rather than jumping at midnight
+    assert!(compute_urgency(&due_in(2), &config, &clock) > compute_urgency(&due_in(20), &config, &clock));
+    assert!(compute_urgency(&due_in(20), &config, &clock) > compute_urgency(&due_in(48), &config, &clock));
+    assert!(compute_urgency(&due_in(2), &config, &clock) < 100.0);
+    assert!(compute_urgency(&due_in(-2), &config, &clock) > 100.0);
+}
+
+#[test]
+fn test_urgency_config() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let task = Task {
+        project: Some("Ops".into()),
+        created_at: clock.as_of(clock.today() - Duration::days(30)).timestamp(),
+        tags: vec!["oncall".into()],
+        ..task("Pager rota", clock.today() + Duration::days(10), 0.0)
+    };
+    let base = compute_urgency(&task, &config, &clock);
+
+    // Each extra term adds its weight
+    let weighted = UrgencyConfig {
+        age: 0.5,
+        age_max_days: 10.0,
+        tags: BTreeMap::from([("oncall".to_string(), 7.0)]),
+        projects: BTreeMap::from([("Ops".to_string(), 3.0)]),
+        ..config.clone()
+    };
+    assert!((compute_urgency(&task, &weighted, &clock) - base - 15.0).abs() < 0.01);
+
+    // Doubling the due coefficient doubles the due-date term
+    let doubled = UrgencyConfig { due: 20.0, ..config.clone() };
+    assert!((compute_urgency(&task, &doubled, &clock) - 2.0 * base).abs() < 0.01);
+
+    let overdue = Task { due_date: clock.today() - Duration::days(3), ..task };
+    let lenient = UrgencyConfig { overdue: 50.0, overdue_per_day: 0.0, ..config.clone() };
+    assert!(compute_urgency(&overdue, &lenient, &clock) < 60.0);
+
+    assert_eq!(config.level(51.0), UrgencyLevel::High);
+    assert_eq!(config.level(50.0), UrgencyLevel::Medium);
+    assert_eq!(config.level(5.0), UrgencyLevel::Low);
+}
+
+#[test]
+fn test_explain_urgency() {
+    let mut config = UrgencyConfig::default();
+    config.tags.insert("oncall".into(), 10.0);
+    let clock = monday();
+    let task = Task { tags: vec!["oncall".into()], priority: Priority::High, ..task("Late", clock.today() - Duration::days(3), 2.0) };
+
+    let terms = explain_urgency(&task, &config, &clock);
+    let names: Vec<&str> = terms.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["Overdue", "Effort", "Days overdue", "Priority", "Tag +oncall"]);
+    let values: Vec<f64> = terms.iter().map(|t| t.value).collect();
+    assert_eq!(values, vec![100.0, 2.0, 6.0, config.priority, 10.0]);
+    let sum: f64 = terms.iter().map(|t| t.value).sum();
+    assert!((sum - compute_urgency(&task, &config, &clock)).abs() < 1e-9);
+
+    // Blocking and blocked tasks list the adjustment as its own term
+    let blocked = Task { id: 2, uuid: Uuid::new_v4(), depends_on: vec![task.uuid], ..task.clone() };
+    let tasks = vec![task.clone(), blocked.clone()];
+    let terms = explain_urgency_in(&task, &tasks, &config, &clock);
+    assert_eq!(terms.last().unwrap().name, "Blocking 1 task");
+    let terms = explain_urgency_in(&blocked, &tasks, &config, &clock);
+    let penalty = terms.iter().find(|t| t.name == "Blocked").unwrap();
+    assert!(penalty.value < 0.0);
+    let sum: f64 = terms.iter().map(|t| t.value).sum();
+    assert!((sum - compute_urgency_in(&blocked, &tasks, &config, &clock)).abs() < 1e-9);
+
+    // A task due in the future has no overdue terms, and a closed task a single one
+    let future = Task { due_date: clock.today() + Duration::days(5), ..task.clone() };
+    assert!(explain_urgency(&future, &config, &clock).iter().all(|t| !t.name.contains("Overdue")));
+    let done = Task { status: Status::Done, ..task };
+    assert_eq!(explain_urgency(&done, &config, &clock).len(), 1);
+}
+
+#[test]
+fn test_urgency_capacity() {
+    let config = UrgencyConfig { mode: UrgencyMode::Capacity, days_per_week: 7, ..UrgencyConfig::default() };
+    let clock = monday();
+    let soon = task("Migration", clock.today() + Duration::days(3), 40.0);
+    let later = Task { id: 2, due_date: clock.today() + Duration::days(21), ..soon.clone() };
+
+    // Four working days of 8 hours are left for 40 hours of work
+    assert_eq!(available_hours(&soon, &config, &clock), 32.0);
+    assert_eq!(slack(&soon, &config, &clock), -8.0);
+    assert!(is_at_risk(&soon, &config, &clock));
+    assert!(!is_at_risk(&later, &config, &clock));
+    assert!(compute_urgency(&soon, &config, &clock) > compute_urgency(&later, &config, &clock));
+    assert!(explain_urgency(&soon, &config, &clock).iter().any(|t| t.name == "At risk"));
+
+    // Hours worked come off the work left
+    let started = Task { work_log: vec![WorkEntry { date: clock.today(), hours: 10.0, note: None }], ..soon.clone() };
+    assert_eq!(started.remaining_hours(), 30.0);
+    assert!(!is_at_risk(&started, &config, &clock));
+    assert!(compute_urgency(&started, &config, &clock) < compute_urgency(&soon, &config, &clock));
+
+    // With a five-day week, any two weeks have ten working days
+    let weekdays = UrgencyConfig { days_per_week: 5, ..config.clone() };
+    let fortnight = Task { due_date: clock.today() + Duration::days(13), ..soon.clone() };
+    assert_eq!(available_hours(&fortnight, &weekdays, &clock), 80.0);
+
+    // Closed and overdue tasks are never at risk, and deadline mode flags without scoring it
+    assert!(!is_at_risk(&Task { status: Status::Done, ..soon.clone() }, &config, &clock));
+    assert!(!is_at_risk(&Task { due_date: clock.today() - Duration::days(1), ..soon.clone() }, &config, &clock));
+    let deadline = UrgencyConfig { days_per_week: 7, ..UrgencyConfig::default() };
+    assert!(is_at_risk(&soon, &deadline, &clock));
+    assert!(explain_urgency(&soon, &deadline, &clock).iter().all(|t| t.name != "At risk"));
+}
+
+struct ShortestName;
+
+impl UrgencyStrategy for ShortestName {
+    fn name(&self) -> &str {
+        "shortest-name"
+    }
+
+    fn explain(&self, task: &Task, _tasks: &[Task], _config: &UrgencyConfig, _clock: &Clock) -> Vec<Contribution> {
+        vec![Contribution { name: "Name length".into(), value: -(task.name.len() as f64) }]
+    }
+}
+
+#[test]
+fn test_urgency_strategies() {
+    let config = UrgencyConfig::default();
+    let clock = monday();
+    let big = Task { priority: Priority::High, ..task("Big important", clock.today() + Duration::days(10), 8.0) };
+    let soon = Task { id: 2, ..task("Soon", clock.today() + Duration::days(2), 1.0) };
+    let quick = Task { id: 3, priority: Priority::Medium, ..task("Quick win", clock.today() + Duration::days(20), 0.5) };
+    let done = Task { id: 4, status: Status::Done, ..task("Done", clock.today() - Duration::days(5), 8.0) };
+    let all = vec![big, soon, quick, done];
+    let order = |name: &str| {
+        let mut tasks = all.clone();
+        sort_by_urgency(&mut tasks, &all, strategy(name).unwrap().as_ref(), &config, &clock);
+        tasks.iter().map(|t| t.id).collect::<Vec<_>>()
+    };
+
+    // Closed tasks sort last whatever the strategy
+    assert_eq!(order("edf"), vec![2, 1, 3, 4]);
+    assert_eq!(order("wsjf"), vec![3, 1, 2, 4]);
+    assert_eq!(order("priority"), vec![1, 3, 2, 4]);
+    assert_eq!(order("standard").last(), Some(&4));
+    let standard = strategy("standard").unwrap();
+    assert_eq!(standard.urgency(&all[0], &all, &config, &clock), compute_urgency_in(&all[0], &all, &config, &clock));
+    assert_eq!(strategy("edf").unwrap().level(-0.5, &config), UrgencyLevel::High);
+
+    // Library users can add their own
+    assert!(matches!(strategy("shortest-name"), Err(TaskustError::InvalidArgument(_))));
+    register_strategy(ShortestName);
+    assert!(strategy_names().contains(&"shortest-name".to_string()));
+    assert_eq!(order("shortest-name"), vec![2, 3, 1, 4]);
 }